@@ -1,8 +1,24 @@
 use screenshots::{self, Screen, DisplayInfo};
 use winit::dpi::PhysicalPosition;
 use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use image::codecs::webp::{WebPEncoder, WebPQuality};
 use log::{info, error};
 mod image_proc;
+mod ocr;
+mod config;
+mod pdf;
+mod project;
+mod excalidraw;
+mod vault;
+mod beautify;
+mod trim;
+mod scroll;
+pub mod window;
+pub mod action;
+pub mod annotate;
+pub mod redact;
 use image;
 use crate::gui::{self, App};
 
@@ -46,38 +62,144 @@ pub fn parse(args: Vec<String>) -> () {
                 }
 
                 info!("{:?}\n{:?}", pos, br);
-                let app = gui::run(pos, br);
+                let config = config::Config::load();
+                let mut composite = config.get("composite") == Some("true");
+                let mut json = false;
+                let mut project = config.get("project") == Some("true");
+
+                let mut i = j+1;
+                while i < args.len(){
+                    match &args[i][..] {
+                        "--composite" => composite = true,
+                        "--separate"  => composite = false,
+                        "--json"      => json = true,
+                        "--project"   => project = true,
+                        _             => break,
+                    }
+                    i = i+1;
+                }
+                j = i-1;
+
+                let settings = gui::Settings {
+                    frame: Some(image_proc::frame(&image_proc::Point { x: pos.x as i32, y: pos.y as i32 },
+                                                  &image_proc::Point { x: br.x as i32, y: br.y as i32 })),
+                    windows: window::list().iter().map(|w| {
+                        let (x, y, width, height) = w.bounds(true);
+                        iced::Rectangle { x: x as f32, y: y as f32, width: width as f32, height: height as f32 }
+                    }).collect(),
+                    composite,
+                    json,
+                    project,
+                    ..overlay(&config)
+                };
+                let app = gui::run(pos, br, settings);
                 ()
             },
 
+            // reopen a .pictura project in the overlay over its original pixels
+            "edit"       | "--edit" => {
+                if j+1 < args.len() {
+                    match project::load(Path::new(&args[j+1])) {
+                        Ok((project, image)) => {
+                            let config = config::Config::load();
+                            let tl = PhysicalPosition::new(project.origin.0 as f64, project.origin.1 as f64);
+                            let br = PhysicalPosition::new(tl.x + image.width() as f64, tl.y + image.height() as f64);
+                            let settings = gui::Settings {
+                                frame: Some(image),
+                                composite: project.composite,
+                                project: true,
+                                regions: project.regions.iter().map(|r| iced::Rectangle {
+                                    x: r.x as f32, y: r.y as f32, width: r.width as f32, height: r.height as f32,
+                                }).collect(),
+                                annotations: project.annotations,
+                                ..overlay(&config)
+                            };
+                            gui::run(tl, br, settings);
+                        }
+                        Err(e)               => error!("Could not open {}: {}", args[j+1], e),
+                    }
+                    j = j+1;
+                }
+            },
+
             // text extraction mode
-            // TODO: WIP
             "--text"     | "-T" => {
-                println!("AI text extraction mode enabled");
-                // TODO: add AI functionality  
-                if args.len()>1{
-                    let mut i = j+1;
-                    while i < args.len(){
-                        match &args[i][..] {
-                            "-o"  =>  {
-                                println!("Output to file {}", &args[i+1][..]);
-                                i = i+1;
+                info!("Text extraction mode enabled");
+                let config = config::Config::load();
+                let mut options = ocr::Options::default();
+                if let Some(lang) = config.get("lang") { options.lang = lang.to_string(); }
+                if let Some(psm) = config.get("psm").and_then(ocr::Psm::parse) { options.psm = psm; }
+                if let Some(layout) = config.get("layout").and_then(ocr::Layout::parse) { options.layout = layout; }
+                if let Some(table) = config.get("table").and_then(ocr::TableFormat::parse) { options.table = Some(table); }
+                let mut output = None;
+                let mut verbose = false;
+                let mut copy = false;
+                let mut inputs = Vec::new();
+
+                let mut i = j+1;
+                while i < args.len(){
+                    match &args[i][..] {
+                        "-o"  =>  {
+                            info!("Output to file {}", &args[i+1][..]);
+                            output = Some(args[i+1].clone());
+                            i = i+1;
+                        }
+                        "-v"  =>  {
+                            info!("Verbose");
+                            verbose = true;
+                        },
+                        "-cp" | "--clipboard" => copy = true,
+                        "-t"  => { 
+                            info!("Wait {} seconds", &args[i+1][..]);
+                            i = i+1;
+                        }
+                        "--lang"  | "-l" => {
+                            options.lang = args[i+1].clone();
+                            i = i+1;
+                        }
+                        "--input" | "-i" => {
+                            inputs.push(args[i+1].clone());
+                            i = i+1;
+                        }
+                        "--psm"          => {
+                            match ocr::Psm::parse(&args[i+1][..]) {
+                                Some(psm) => options.psm = psm,
+                                None      => error!("pictura: invalid page segmentation mode {}", args[i+1]),
                             }
-                            "-v"  =>  {
-                                println!("Verbose");
-                            },
-                            "-cp" =>  println!("Copy to clipboard"),
-                            "-t"  => { 
-                                println!("Wait {} seconds", &args[i+1][..]);
-                                i = i+1;
+                            i = i+1;
+                        }
+                        "--layout"       => {
+                            match ocr::Layout::parse(&args[i+1][..]) {
+                                Some(layout) => options.layout = layout,
+                                None         => error!("pictura: invalid layout {}", args[i+1]),
                             }
-                            _     =>  {
-                                j = i-1;
-                                break;
+                            i = i+1;
+                        }
+                        "--table"        => {
+                            match ocr::TableFormat::parse(&args[i+1][..]) {
+                                Some(table) => options.table = Some(table),
+                                None        => error!("pictura: invalid table format {}", args[i+1]),
                             }
+                            i = i+1;
                         }
-                        i = i+1;
+                        _     =>  break,
                     }
+                    i = i+1;
+                }
+                j = i-1;
+
+                if let Err(e) = ocr::validate_langs(&options.lang) {
+                    error!("pictura: {}", e);
+                } else {
+                    let images = if inputs.is_empty() { image_proc::run(None, (None, None), &[], None, None) }
+                                 else { image_proc::run_input(None, &inputs, &[], None, None) };
+                    let texts = ocr::run(&images, &options);
+                    let text = texts.join("\n");
+                    if verbose || (output.is_none() && !copy) { println!("{}", text); }
+                    if let Some(path) = output {
+                        fs::write(path, &text).unwrap();
+                    }
+                    if copy { action::copy_text(&text); }
                 }
             },
 
@@ -85,46 +207,270 @@ pub fn parse(args: Vec<String>) -> () {
             "--image"    | "-I" => { 
                 println!("Image mode enabled");
                 // DONE: add screenshot functionality
+                let config = config::Config::load();
+                let mut options = ocr::Options::default();
+                if let Some(lang) = config.get("lang") { options.lang = lang.to_string(); }
+                if let Some(psm) = config.get("psm").and_then(ocr::Psm::parse) { options.psm = psm; }
+                let mut format = config.get("format").and_then(Format::parse).unwrap_or(Format::Png);
+                let mut points = (None, None);
+                let mut inputs = Vec::new();
+                let mut target = None;
+                let mut decorations = config.get("decorations") != Some("false");
+                let mut actions = Vec::new();
+                let mut redactions = Vec::new();
+                let mut method = config.get("redact").and_then(redact::Method::parse).unwrap_or(redact::Method::Pixelate);
+                let mut auto_redact = config.get("auto_redact") == Some("true");
+                let mut vault_path = config.get("vault").map(vault::expand);
+                let mut note = None;
+                let mut vault_ocr = config.get("vault_ocr") != Some("false");
+                let mut to_vault = false;
+                let mut trim = None;
+                let mut scrolling = None;
+                let mut beautify = (config.get("beautify") == Some("true")).then(|| beautify::Style::load(&config));
 
-                if args.len()>1{
-                    let mut i = j+1;
-                    while i < args.len(){
-                        match &args[i][..] {
-                            "--output"    | "-o"  => {
-                                let points;
-                                // TODO: harden this
-                                if i+2 < args.len() {
-                                    points = (Some(image_proc::Point{x: args[i+1][..].parse::<i32>().unwrap(), 
-                                        y: args[i+2][..].parse::<i32>().unwrap()}), 
-                                              Some(image_proc::Point{x: args[i+3][..].parse::<i32>().unwrap(), 
-                                                  y: args[i+4][..].parse::<i32>().unwrap()}));
-                                    i = i+4;
-                                    j = i;
-                                }
-                                else { 
-                                    points = (None, None);
-                                }
-                                let compressed_images = image_proc::run(None, points);
-                                let mut k = 0;
-                                for images in compressed_images {
-                                    // TODO: make option and unwrap or for default file location
-                                    fs::write(format!("target/{}.png", k), images).unwrap();
-                                    k = k + 1;
+                let mut i = j+1;
+                while i < args.len(){
+                    match &args[i][..] {
+                        "--output"    | "-o"  => {
+                            // TODO: harden this
+                            if i+4 < args.len() {
+                                points = (Some(image_proc::Point{x: args[i+1][..].parse::<i32>().unwrap(), 
+                                    y: args[i+2][..].parse::<i32>().unwrap()}), 
+                                          Some(image_proc::Point{x: args[i+3][..].parse::<i32>().unwrap(), 
+                                              y: args[i+4][..].parse::<i32>().unwrap()}));
+                                i = i+4;
+                            }
+                        }
+                        "--format"    | "-f"  => {
+                            match Format::parse(&args[i+1][..]) {
+                                Some(f) => format = f,
+                                None    => error!("pictura: invalid format {}", args[i+1]),
+                            }
+                            i = i+1;
+                        }
+                        "--lang"      | "-l"  => {
+                            options.lang = args[i+1].clone();
+                            i = i+1;
+                        }
+                        "--input"     | "-i"  => {
+                            inputs.push(args[i+1].clone());
+                            i = i+1;
+                        }
+                        "--window"    | "-w"  => {
+                            target = Some(args[i+1].clone());
+                            i = i+1;
+                        }
+                        "--active-window"     => target = Some(String::new()),
+                        "--no-decorations"    => decorations = false,
+                        "--decorations"       => decorations = true,
+                        "--clipboard" | "-cp" =>  actions.push(action::Action::Copy),
+                        "--copy-text"         =>  actions.push(action::Action::CopyText),
+                        "--pin"               =>  actions.push(action::Action::Pin),
+                        "--redact"            => {
+                            redactions.push(args[i+1].clone());
+                            i = i+1;
+                        }
+                        "--auto-redact"       => auto_redact = true,
+                        "--no-auto-redact"    => auto_redact = false,
+                        "--scroll"            => {
+                            scrolling.get_or_insert_with(|| scroll::Options::load(&config)).wheel = true;
+                        }
+                        "--scroll-by-hand"    => {
+                            scrolling.get_or_insert_with(|| scroll::Options::load(&config)).wheel = false;
+                        }
+                        "--scroll-frames"     => {
+                            match args[i+1].parse::<usize>() {
+                                Ok(frames) => scrolling.get_or_insert_with(|| scroll::Options::load(&config)).frames = frames,
+                                Err(_)     => error!("pictura: invalid --scroll-frames {}", args[i+1]),
+                            }
+                            i = i+1;
+                        }
+                        "--trim"              => {
+                            // The tolerance is optional
+                            match args.get(i+1).and_then(|t| t.parse::<u8>().ok()) {
+                                Some(tolerance) => {
+                                    trim = Some(tolerance);
+                                    i = i+1;
                                 }
-                                i = i+1;
+                                None            => trim = Some(trim::TOLERANCE),
                             }
-                            "--clipboard" | "-cp" =>  println!("Copy to clipboard"),
-                            "-t"                  => { 
-                                println!("Wait {} seconds", &args[i+1][..]);
-                                i = i+1;
-                            },
-                            _                     =>  {
-                                j = i-1;
-                                break;
+                        }
+                        "--redact-method"     => {
+                            match redact::Method::parse(&args[i+1][..]) {
+                                Some(m) => method = m,
+                                None    => error!("pictura: invalid redaction method {}", args[i+1]),
+                            }
+                            i = i+1;
+                        }
+                        "--vault"             => {
+                            vault_path = Some(vault::expand(&args[i+1]));
+                            to_vault = true;
+                            i = i+1;
+                        }
+                        "--note"              => {
+                            note = Some(vault::Note::Named(args[i+1].clone()));
+                            to_vault = true;
+                            i = i+1;
+                        }
+                        "--daily"             => {
+                            note = Some(vault::Note::Daily);
+                            to_vault = true;
+                        }
+                        "--no-ocr"            => vault_ocr = false,
+                        "--beautify"          => {
+                            beautify.get_or_insert_with(|| beautify::Style::load(&config));
+                        }
+                        "--padding" | "--radius" | "--shadow" => {
+                            let style = beautify.get_or_insert_with(|| beautify::Style::load(&config));
+                            match args[i+1].parse::<u32>() {
+                                Ok(pixels) if args[i] == "--padding" => style.padding = pixels,
+                                Ok(pixels) if args[i] == "--radius"  => style.radius = pixels,
+                                Ok(pixels)                           => style.shadow = pixels,
+                                Err(_)                               => error!("pictura: invalid {} {}", args[i], args[i+1]),
+                            }
+                            i = i+1;
+                        }
+                        "--background"        => {
+                            let style = beautify.get_or_insert_with(|| beautify::Style::load(&config));
+                            match beautify::Background::parse(&args[i+1]) {
+                                Some(background) => style.background = background,
+                                None             => error!("pictura: invalid background {}", args[i+1]),
+                            }
+                            i = i+1;
+                        }
+                        "--chrome"            => beautify.get_or_insert_with(|| beautify::Style::load(&config)).chrome = true,
+                        "--save-as"           => {
+                            actions.push(action::Action::SaveAs(Some(args[i+1].clone())));
+                            i = i+1;
+                        }
+                        "-t"                  => { 
+                            println!("Wait {} seconds", &args[i+1][..]);
+                            i = i+1;
+                        },
+                        _                     =>  break,
+                    }
+                    i = i+1;
+                }
+                j = i-1;
+
+                // Whatever runs OCR needs its models, checked before capturing like --text does
+                let pdf = |path: &str| Path::new(path).extension().and_then(|e| Format::parse(&e.to_string_lossy())) == Some(Format::Pdf);
+                let recognizes = format == Format::Pdf || auto_redact || (to_vault && note.is_some() && vault_ocr)
+                    || actions.iter().any(|action| match action {
+                        action::Action::CopyText           => true,
+                        action::Action::SaveAs(Some(path)) => pdf(path),
+                        _                                  => false,
+                    });
+                if recognizes {
+                    if let Err(e) = ocr::validate_langs(&options.lang) {
+                        error!("pictura: {}", e);
+                        j = j+1;
+                        continue;
+                    }
+                }
+
+                // An empty target is the active window
+                if let Some(target) = target {
+                    let found = if target.is_empty() { window::active() } else { window::find(&target) };
+                    match found {
+                        Some(found) => {
+                            info!("Window {:#x} {:?}", found.id, found.title);
+                            // Only what of it is on the screens, it may be dragged partly off them
+                            let (x, y, width, height) = found.bounds(decorations);
+                            match image_proc::on_screens(x, y, width, height) {
+                                Some((tl, br)) => points = (Some(tl), Some(br)),
+                                None           => {
+                                    println!("pictura: window {:#x} is off the screens", found.id);
+                                    j = j+1;
+                                    continue;
+                                }
                             }
                         }
-                        i = i+1;
+                        None        => {
+                            println!("pictura: no window matching {:?}", target);
+                            j = j+1;
+                            continue;
+                        }
+                    }
+                }
+
+                let redactions: Vec<redact::Redaction> = redactions.iter().filter_map(|spec| {
+                    let redaction = redact::Redaction::parse(spec, method);
+                    if redaction.is_none() { error!("pictura: invalid redaction {}, expected x,y,w,h", spec); }
+                    redaction
+                }).collect();
+                let patterns = config.prefixed("redact");
+                let auto = if auto_redact { Some(redact::Auto::new(&patterns, method, &options)) } else { None };
+                let images = match &scrolling {
+                    Some(scrolling) => {
+                        // Image files are taken as the frames, in order
+                        let (frames, origin) = match points {
+                            _ if !inputs.is_empty() => {
+                                let frames: Vec<image::RgbaImage> = inputs.iter().filter_map(|input| match image_proc::load(input) {
+                                    Ok(image) => Some(image),
+                                    Err(e)    => {
+                                        error!("Could not read {}: {}", input, e);
+                                        None
+                                    }
+                                }).collect();
+                                (frames, image_proc::Point { x: 0, y: 0 })
+                            }
+                            (Some(a), Some(b))      => {
+                                let tl = image_proc::Point { x: a.x.min(b.x), y: a.y.min(b.y) };
+                                let br = image_proc::Point { x: a.x.max(b.x), y: a.y.max(b.y) };
+                                (scroll::capture(&tl, &br, scrolling), tl)
+                            }
+                            _                       => {
+                                println!("pictura: --scroll needs a region, pass --output or --window");
+                                j = j+1;
+                                continue;
+                            }
+                        };
+                        image_proc::run_stitched(None, &frames, &origin, &redactions, auto.as_ref(), trim)
+                    }
+                    None if inputs.is_empty() => image_proc::run(None, points, &redactions, auto.as_ref(), trim),
+                    None                      => image_proc::run_input(None, &inputs, &redactions, auto.as_ref(), trim),
+                };
+                let images = match &beautify {
+                    Some(style) => image_proc::transform(images, |_, image| beautify::apply(&image, style)),
+                    None        => images,
+                };
+                // --note and --daily fall back on the configured vault
+                if to_vault {
+                    match vault_path {
+                        Some(vault) => actions.push(action::Action::Vault(vault::Target { vault, note, ocr: vault_ocr })),
+                        None        => error!("pictura: no vault, pass --vault or set `vault` in the config"),
+                    }
+                }
+                if actions.is_empty() { actions.push(action::Action::Save); }
+                for action in &actions {
+                    action::dispatch(action, action::Capture::Images { images: images.clone(), format, options: &options });
+                }
+            },
+            // serve the clipboard for a capture that has already finished, see action::hand_off
+            "--serve-clipboard" => {
+                if j+1 < args.len() {
+                    let mut data = Vec::new();
+                    match io::stdin().read_to_end(&mut data) {
+                        Ok(_)  => action::serve(&args[j+1], data),
+                        Err(e) => error!("Could not read the clipboard contents: {}", e),
+                    }
+                    j = j+1;
+                }
+            },
+            // show an image in a window that stays on top
+            "--pin"             => {
+                if j+1 < args.len() {
+                    match image_proc::load(&args[j+1]) {
+                        Ok(image) => gui::pin::run(image).unwrap(),
+                        Err(e)    => error!("Could not read {}: {}", args[j+1], e),
                     }
+                    j = j+1;
+                }
+            },
+            "--list-langs"      => {
+                for lang in ocr::available_langs() {
+                    println!("{}", lang);
                 }
             },
             "--display-info"    => {
@@ -149,13 +495,86 @@ pub fn parse(args: Vec<String>) -> () {
 
 }
 
-pub fn capture(app: (PhysicalPosition<f64>, PhysicalPosition<f64>)) {
-    let points = (Some(image_proc::Point{x: app.0.x as i32, y: app.0.y as i32 }),
-                  Some(image_proc::Point{x: app.1.x as i32, y: app.1.y as i32 }));
-    let compressed_images = image_proc::run(None, points);
-    let mut k = 0;
-    for images in compressed_images {
-        fs::write(format!("target/{}.png", k), images).unwrap();
-        k = k + 1;
+// File formats a capture can be written as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Png,
+    Webp,       // lossless, keeps the alpha of beautified captures like png
+    Pdf,        // searchable, the OCR text sits invisibly on top of the image
+    Excalidraw, // a scene with the image embedded and the annotations as editable shapes
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match &*name.to_lowercase() {
+            "png"        => Some(Format::Png),
+            "webp"       => Some(Format::Webp),
+            "pdf"        => Some(Format::Pdf),
+            "excalidraw" => Some(Format::Excalidraw),
+            _            => None,
+        }
+    }
+}
+
+// The overlay's look and selection aids from the config.
+fn overlay(config: &config::Config) -> gui::Settings {
+    gui::Settings {
+        border_width: config.get("border_width").and_then(|w| w.parse::<f32>().ok()),
+        border_color: config.color("border_color")
+            .map(|[r, g, b, a]| iced::Color::from_rgba8(r, g, b, a as f32 / 255.0)),
+        loupe_size: config.get("loupe_size").and_then(|n| n.parse::<usize>().ok()),
+        loupe_zoom: config.get("loupe_zoom").and_then(|z| z.parse::<f32>().ok()),
+        ratio: config.ratio("ratio"),
+        presets: config.presets("presets"),
+        ..Default::default()
+    }
+}
+
+// Write the png encoded images of one run to disk, a pdf gets one page per image. Returns the first file.
+fn save(images: Vec<Vec<u8>>, format: Format, options: &ocr::Options, shapes: &[Vec<annotate::Annotation>]) -> PathBuf {
+    match format {
+        Format::Png => {
+            for (k, image) in images.iter().enumerate() {
+                // TODO: make option and unwrap or for default file location
+                fs::write(format!("target/{}.png", k), image).unwrap();
+            }
+            PathBuf::from("target/0.png")
+        }
+        Format::Webp => {
+            for (k, image) in images.iter().enumerate() {
+                fs::write(format!("target/{}.webp", k), webp(image)).unwrap();
+            }
+            PathBuf::from("target/0.webp")
+        }
+        Format::Pdf => {
+            fs::write("target/capture.pdf", document(&images, options)).unwrap();
+            PathBuf::from("target/capture.pdf")
+        }
+        Format::Excalidraw => {
+            fs::write("target/capture.excalidraw", excalidraw::scene(&images, shapes)).unwrap();
+            PathBuf::from("target/capture.excalidraw")
+        }
     }
 }
+
+// A png encoded image as a lossless webp, alpha and all.
+fn webp(png: &[u8]) -> Vec<u8> {
+    let image = image::load_from_memory(png).unwrap().to_rgba8();
+    let mut buffer = Vec::new();
+    WebPEncoder::new_with_quality(&mut buffer, WebPQuality::lossless())
+        .encode(image.as_raw(), image.width(), image.height(), image::ColorType::Rgba8)
+        .unwrap();
+    buffer
+}
+
+// A searchable pdf with one page per png encoded image.
+fn document(images: &[Vec<u8>], options: &ocr::Options) -> Vec<u8> {
+    let mut words = ocr::words(images, options);
+    words.resize(images.len(), Vec::new());
+    let pages: Vec<(image::RgbImage, Vec<ocr::Word>)> = images
+        .iter()
+        .map(|image| image::load_from_memory(image).unwrap().to_rgb8())
+        .zip(words)
+        .collect();
+    pdf::document(&pages)
+}
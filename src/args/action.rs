@@ -0,0 +1,297 @@
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use arboard::{Clipboard, ImageData, SetExtLinux};
+use log::{info, warn, error};
+
+use super::annotate::{self, Annotation};
+use super::{beautify, config, excalidraw, image_proc, ocr, project, vault, Format};
+
+/* What happens to a finished capture. The overlay's toolbar and the CLI
+ * flags both end up here, so every action behaves the same either way.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Save,                   // to the default location
+    SaveAs(Option<String>), // to a path, asked for when there is none
+    Copy,                   // the image to the clipboard
+    CopyText,               // the OCR text to the clipboard
+    Annotate,
+    Pin,                    // keep it on screen in a window of its own
+    Vault(vault::Target),   // into an Obsidian vault, optionally embedded in a note
+    Cancel,
+}
+
+// A finished capture on its way to an action.
+pub enum Capture<'a> {
+    // Taken by the CLI flags, png encoded
+    Images { images: Vec<Vec<u8>>, format: Format, options: &'a ocr::Options },
+    // Selected in the --gui overlay, still to be cut out of the frozen screens
+    Overlay(Selection<'a>),
+}
+
+// What the overlay hands over, in global physical pixels.
+pub struct Selection<'a> {
+    pub frame: Option<(&'a image::RgbaImage, (i32, i32))>, // the frozen screens and their top left corner
+    pub regions: Vec<iced::Rectangle>,                     // the last is the selection
+    pub annotations: Vec<Annotation>,
+    pub composite: bool, // paste all regions into one image instead of one each
+    pub json: bool,      // print the regions as JSON
+    pub project: bool,   // keep an annotated capture editable as a .pictura project
+}
+
+/* Run `action` on a capture, the one way the toolbar and the CLI flags
+ * both take. Returns where it was saved if it was.
+ */
+pub fn dispatch(action: &Action, capture: Capture) -> Option<PathBuf> {
+    match capture {
+        Capture::Images { images, format, options } => run(action, images, format, options, &[]),
+        Capture::Overlay(selection)                 => overlay(action, selection),
+    }
+}
+
+/* Run `action` on the png encoded images of one capture, returning where
+ * they were saved if they were. `shapes` are annotations per image, in its
+ * pixels, that aren't drawn in yet: an excalidraw scene keeps them as its
+ * own elements, everything else gets them drawn in first.
+ */
+fn run(action: &Action, images: Vec<Vec<u8>>, format: Format, options: &ocr::Options, shapes: &[Vec<Annotation>])
+    -> Option<PathBuf> {
+    if images.is_empty() && *action != Action::Cancel {
+        warn!("Nothing captured");
+        return None;
+    }
+    info!("Action: {:?}", action);
+    let (action, format) = resolve(action.clone(), format)?;
+    let saving = matches!(action, Action::Save | Action::SaveAs(_) | Action::Vault(_));
+    let images = if saving && format == Format::Excalidraw { images } else { flatten(images, shapes) };
+    match &action {
+        Action::Save         => return Some(super::save(images, format, options, shapes)),
+        Action::SaveAs(path) => {
+            let path = PathBuf::from(path.as_deref().unwrap_or_default());
+            return save_as(images, format, options, shapes, &path).then_some(path);
+        }
+        Action::Copy         => {
+            if images.len() > 1 { warn!("Copying the first of {} images, use --composite for all", images.len()); }
+            hand_off("image", &images[0]);
+        }
+        Action::CopyText     => copy_text(&ocr::run(&images, options).join("\n")),
+        Action::Annotate     => {
+            warn!("Annotating needs the --gui overlay, saving instead");
+            return Some(super::save(images, format, options, &[]));
+        }
+        Action::Pin          => {
+            // A process of its own per image, so the windows outlive this one
+            for image in images {
+                let child = Command::new(env::current_exe().unwrap())
+                    .args(["--pin", "-"])
+                    .stdin(Stdio::piped())
+                    .spawn();
+                match child {
+                    Ok(mut child) => { child.stdin.take().unwrap().write_all(&image).unwrap(); }
+                    Err(e)        => error!("Could not pin the capture: {}", e),
+                }
+            }
+        }
+        Action::Vault(target) => return vault::save(target, &images, format, options, shapes),
+        Action::Cancel       => info!("Cancelled"),
+    }
+    None
+}
+
+/* Cut the regions of `selection` out of its frame, when there is one,
+ * with the annotations drawn in, each as its own image or a single
+ * composite, and run `action` on them. Format, OCR and beautify come from
+ * the config.
+ */
+fn overlay(action: &Action, selection: Selection) -> Option<PathBuf> {
+    if *action == Action::Cancel { return None; }
+    let regions: Vec<image_proc::Rectangle> = selection.regions.iter().map(|r| image_proc::Rectangle {
+        tl: image_proc::Point { x: r.x.round() as i32, y: r.y.round() as i32 },
+        br: image_proc::Point { x: (r.x + r.width).round() as i32, y: (r.y + r.height).round() as i32 },
+    }).collect();
+    if selection.json {
+        let objects: Vec<String> = regions.iter().map(|r| {
+            format!("{{\"x\": {}, \"y\": {}, \"width\": {}, \"height\": {}}}",
+                    r.tl.x, r.tl.y, r.br.x - r.tl.x, r.br.y - r.tl.y)
+        }).collect();
+        println!("[{}]", objects.join(", "));
+    }
+    let config = config::Config::load();
+    let mut options = ocr::Options::default();
+    if let Some(lang) = config.get("lang") { options.lang = lang.to_string(); }
+    if let Some(psm) = config.get("psm").and_then(ocr::Psm::parse) { options.psm = psm; }
+    let format = config.get("format").and_then(Format::parse).unwrap_or(Format::Png);
+    // Save as may pick another format, the shapes are split up for the one it ends up with
+    let (action, format) = resolve(action.clone(), format)?;
+
+    let annotations = &selection.annotations;
+    let origin = selection.frame.map(|(_, (x, y))| image_proc::Point { x, y });
+    let frozen = selection.frame.map(|(frame, _)| frame).zip(origin.as_ref());
+    // An excalidraw scene keeps the shapes as its own elements, only redactions go into the pixels
+    let (drawn, shapes): (Vec<Annotation>, Vec<Annotation>) = if format == Format::Excalidraw {
+        annotations.iter().cloned().partition(|a| a.redaction().is_some())
+    } else {
+        (annotations.to_vec(), Vec::new())
+    };
+    let images = image_proc::run_regions(None, frozen, &regions, &drawn, selection.composite);
+    let mut shapes = per_image(&regions, &shapes, selection.composite);
+    let (images, shapes) = if config.get("beautify") == Some("true") {
+        let style = beautify::Style::load(&config);
+        let (x, y) = style.offset();
+        shapes.iter_mut().flatten().for_each(|shape| shape.translate(x as f32, y as f32));
+        (image_proc::transform(images, |_, image| beautify::apply(&image, &style)), shapes)
+    } else {
+        (images, shapes)
+    };
+    let saved = run(&action, images, format, &options, &shapes);
+
+    // Annotated captures stay editable with `pictura edit` when asked to
+    if let (Some(saved), Some((frame, origin)), true, false) = (&saved, frozen, selection.project, annotations.is_empty()) {
+        match project::save(saved, frame, origin, &regions, annotations, selection.composite) {
+            Ok(path) => println!("Project saved to {}", path.display()),
+            Err(e)   => error!("Could not save the project: {}", e),
+        }
+    }
+    saved
+}
+
+/* `shapes` in the pixels of each image `run_regions` cuts out of the
+ * regions, those of one region for each, or all of them for a composite.
+ */
+fn per_image(regions: &[image_proc::Rectangle], shapes: &[Annotation], composite: bool) -> Vec<Vec<Annotation>> {
+    if shapes.is_empty() || regions.is_empty() { return Vec::new(); }
+    let tl = (regions.iter().map(|r| r.tl.x).min().unwrap(), regions.iter().map(|r| r.tl.y).min().unwrap());
+    let place = |(x, y): (i32, i32), keep: &dyn Fn(&Annotation) -> bool| -> Vec<Annotation> {
+        shapes.iter().filter(|a| keep(a)).map(|a| {
+            let mut a = a.clone();
+            a.translate(-x as f32, -y as f32);
+            a
+        }).collect()
+    };
+    if composite { return vec![place(tl, &|_| true)]; }
+    regions.iter().map(|r| {
+        place((r.tl.x, r.tl.y), &|a: &Annotation| {
+            let (left, top, right, bottom) = a.bounds();
+            left < r.br.x as f32 && right > r.tl.x as f32 && top < r.br.y as f32 && bottom > r.tl.y as f32
+        })
+    }).collect()
+}
+
+/* The format `action` ends up saving in: a path's extension picks it, so
+ * Save as asks for the path first and comes back with it. None when the
+ * dialog is cancelled.
+ */
+pub fn resolve(action: Action, format: Format) -> Option<(Action, Format)> {
+    let path = match action {
+        Action::SaveAs(Some(path)) => PathBuf::from(path),
+        Action::SaveAs(None)       => ask(format)?,
+        action                     => return Some((action, format)),
+    };
+    let format = path.extension()
+        .and_then(|ext| Format::parse(&ext.to_string_lossy()))
+        .unwrap_or(format);
+    Some((Action::SaveAs(Some(path.to_string_lossy().into_owned())), format))
+}
+
+// Put `text` on the clipboard, see `hand_off`.
+pub fn copy_text(text: &str) {
+    hand_off("text", text.as_bytes());
+}
+
+/* X11 only serves the clipboard while its owner is alive, so a process
+ * of its own takes `data` over, like a pinned capture, and keeps serving
+ * it after this one is done.
+ */
+fn hand_off(kind: &str, data: &[u8]) {
+    let child = Command::new(env::current_exe().unwrap())
+        .args(["--serve-clipboard", kind])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn();
+    match child {
+        Ok(mut child) => { child.stdin.take().unwrap().write_all(data).unwrap(); }
+        Err(e)        => error!("Could not copy to the clipboard: {}", e),
+    }
+}
+
+/* The other end of `hand_off`: serve `data`, text or a png encoded image,
+ * blocking until another program takes the clipboard over.
+ */
+pub fn serve(kind: &str, data: Vec<u8>) {
+    match kind {
+        "image" => {
+            let image = match image::load_from_memory(&data) {
+                Ok(image) => image.to_rgba8(),
+                Err(e)    => {
+                    error!("Could not read the image to copy: {}", e);
+                    return;
+                }
+            };
+            let (width, height) = (image.width() as usize, image.height() as usize);
+            clipboard(|clipboard| clipboard.set().wait().image(ImageData {
+                width,
+                height,
+                bytes: Cow::Owned(image.into_raw()),
+            }));
+        }
+        _       => clipboard(|clipboard| clipboard.set().wait().text(String::from_utf8_lossy(&data))),
+    }
+}
+
+fn clipboard(set: impl FnOnce(&mut Clipboard) -> Result<(), arboard::Error>) {
+    let result = Clipboard::new().and_then(|mut clipboard| set(&mut clipboard));
+    if let Err(e) = result {
+        error!("Could not copy to the clipboard: {}", e);
+    }
+}
+
+// Draw the shapes kept apart into their images.
+fn flatten(images: Vec<Vec<u8>>, shapes: &[Vec<Annotation>]) -> Vec<Vec<u8>> {
+    if shapes.iter().all(Vec::is_empty) { return images; }
+    image_proc::transform(images, |k, mut image| {
+        annotate::rasterize(&mut image, shapes.get(k).map(|s| &s[..]).unwrap_or(&[]), (0.0, 0.0));
+        image
+    })
+}
+
+// Several pngs are numbered `name-0.png`, `name-1.png`, ... False when a file couldn't be written.
+fn save_as(images: Vec<Vec<u8>>, format: Format, options: &ocr::Options, shapes: &[Vec<Annotation>], path: &Path) -> bool {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let numbered = |k: usize, extension: &str| path.with_file_name(format!("{}-{}.{}", stem, k, extension));
+    let files: Vec<(PathBuf, Vec<u8>)> = match format {
+        Format::Png if images.len() == 1 => vec![(path.to_path_buf(), images[0].clone())],
+        Format::Png        => images.iter().enumerate().map(|(k, image)| (numbered(k, "png"), image.clone())).collect(),
+        Format::Webp if images.len() == 1 => vec![(path.to_path_buf(), super::webp(&images[0]))],
+        Format::Webp       => images.iter().enumerate().map(|(k, image)| (numbered(k, "webp"), super::webp(image))).collect(),
+        Format::Pdf        => vec![(path.to_path_buf(), super::document(&images, options))],
+        Format::Excalidraw => vec![(path.to_path_buf(), excalidraw::scene(&images, shapes).into_bytes())],
+    };
+    for (path, contents) in &files {
+        if let Err(e) = fs::write(path, contents) {
+            error!("Could not save {}: {}", path.display(), e);
+            return false;
+        }
+    }
+    println!("Saved to {}", path.display());
+    true
+}
+
+fn ask(format: Format) -> Option<PathBuf> {
+    let name = match format {
+        Format::Png        => "capture.png",
+        Format::Webp       => "capture.webp",
+        Format::Pdf        => "capture.pdf",
+        Format::Excalidraw => "capture.excalidraw",
+    };
+    rfd::FileDialog::new()
+        .set_file_name(name)
+        .add_filter("PNG", &["png"])
+        .add_filter("WebP", &["webp"])
+        .add_filter("PDF", &["pdf"])
+        .add_filter("Excalidraw", &["excalidraw"])
+        .save_file()
+}
@@ -0,0 +1,327 @@
+use image::RgbaImage;
+use font8x8::{UnicodeFonts, BASIC_FONTS, LATIN_FONTS};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+use super::redact::{Method, Redaction};
+
+// A point in global physical pixels.
+pub type Point = (f32, f32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Arrow,
+    Rectangle,
+    Ellipse,
+    Line,
+    Pen,
+    Text,
+    Redact(Method),
+}
+
+impl Tool {
+    pub const ALL: [Tool; 9] = [
+        Tool::Arrow, Tool::Rectangle, Tool::Ellipse, Tool::Line, Tool::Pen, Tool::Text,
+        Tool::Redact(Method::Pixelate), Tool::Redact(Method::Fill), Tool::Redact(Method::Blur),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Arrow     => "Arrow",
+            Tool::Rectangle => "Box",
+            Tool::Ellipse   => "Ellipse",
+            Tool::Line      => "Line",
+            Tool::Pen       => "Pen",
+            Tool::Text      => "Text",
+            Tool::Redact(Method::Pixelate) => "Pixelate",
+            Tool::Redact(Method::Fill)     => "Redact",
+            Tool::Redact(Method::Blur)     => "Blur",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    Arrow(Point, Point),     // from the tail to the head
+    Rectangle(Point, Point), // opposite corners
+    Ellipse(Point, Point),   // opposite corners of its bounding box
+    Line(Point, Point),
+    Pen(Vec<Point>),
+    Text(Point, String),     // top left corner
+    Redact(Point, Point, Method), // opposite corners, applied to the pixels before any drawing
+}
+
+// A shape with its stroke, the color is rgba and text is drawn `width` + 1 pixels per font pixel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub shape: Shape,
+    pub color: [u8; 4],
+    pub width: f32,
+}
+
+impl Annotation {
+    // A shape of `tool` starting at `at`, grown by `drag`.
+    pub fn new(tool: Tool, at: Point, color: [u8; 4], width: f32) -> Annotation {
+        let shape = match tool {
+            Tool::Arrow     => Shape::Arrow(at, at),
+            Tool::Rectangle => Shape::Rectangle(at, at),
+            Tool::Ellipse   => Shape::Ellipse(at, at),
+            Tool::Line      => Shape::Line(at, at),
+            Tool::Pen       => Shape::Pen(vec![at]),
+            Tool::Text      => Shape::Text(at, String::new()),
+            Tool::Redact(method) => Shape::Redact(at, at, method),
+        };
+        Annotation { shape, color, width }
+    }
+
+    // Move the end of the shape to `to`, or add it to a freehand stroke.
+    pub fn drag(&mut self, to: Point) {
+        match &mut self.shape {
+            Shape::Arrow(_, end)
+            | Shape::Rectangle(_, end)
+            | Shape::Ellipse(_, end)
+            | Shape::Line(_, end)
+            | Shape::Redact(_, end, _) => *end = to,
+            Shape::Pen(points)         => points.push(to),
+            Shape::Text(..)            => {}
+        }
+    }
+
+    // The region a redaction covers, rounded out to whole pixels.
+    pub fn redaction(&self) -> Option<Redaction> {
+        let (a, b, method) = match &self.shape {
+            Shape::Redact(a, b, method) => (a, b, *method),
+            _                           => return None,
+        };
+        let (left, top) = (a.0.min(b.0).floor(), a.1.min(b.1).floor());
+        let (right, bottom) = (a.0.max(b.0).ceil(), a.1.max(b.1).ceil());
+        Some(Redaction {
+            x: left as i32,
+            y: top as i32,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+            method,
+        })
+    }
+
+    // Move the whole shape by (dx, dy).
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        let by = |p: &mut Point| *p = (p.0 + dx, p.1 + dy);
+        match &mut self.shape {
+            Shape::Arrow(a, b)
+            | Shape::Rectangle(a, b)
+            | Shape::Ellipse(a, b)
+            | Shape::Line(a, b)
+            | Shape::Redact(a, b, _) => { by(a); by(b); }
+            Shape::Pen(points)       => points.iter_mut().for_each(by),
+            Shape::Text(at, _)       => by(at),
+        }
+    }
+
+    // Left, top, right and bottom of every pixel the shape touches.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        if let Shape::Redact(a, b, _) = &self.shape {
+            return (a.0.min(b.0), a.1.min(b.1), a.0.max(b.0), a.1.max(b.1));
+        }
+        let (segments, squares) = self.outline();
+        extent(&segments, &squares, self.width / 2.0 + 1.0)
+    }
+
+    // Whether `point` is on the stroke, give or take `slop` pixels. Redactions and text are hit anywhere inside.
+    pub fn hit(&self, point: Point, slop: f32) -> bool {
+        match &self.shape {
+            Shape::Redact(..) | Shape::Text(..) => {
+                let (left, top, right, bottom) = self.bounds();
+                point.0 >= left - slop && point.0 <= right + slop && point.1 >= top - slop && point.1 <= bottom + slop
+            }
+            _ => self.outline().0.iter().any(|(a, b)| distance(point, *a, *b) <= self.width / 2.0 + slop),
+        }
+    }
+
+    // Pixels per font pixel of a text label.
+    pub fn text_scale(&self) -> f32 {
+        (self.width + 1.0).round()
+    }
+
+    // Line segments and filled squares (x, y, side) the shape is made of.
+    fn outline(&self) -> (Vec<(Point, Point)>, Vec<(f32, f32, f32)>) {
+        let mut segments = Vec::new();
+        let mut squares = Vec::new();
+        match &self.shape {
+            Shape::Line(a, b)      => segments.push((*a, *b)),
+            Shape::Arrow(a, b)     => {
+                segments.push((*a, *b));
+                // Two barbs swept back from the head
+                let angle = (b.1 - a.1).atan2(b.0 - a.0);
+                let length = (self.width * 4.0).max(12.0);
+                for side in [-0.5, 0.5] {
+                    let barb = (b.0 - length * (angle + side).cos(), b.1 - length * (angle + side).sin());
+                    segments.push((*b, barb));
+                }
+            }
+            Shape::Rectangle(a, b) => {
+                let corners = [*a, (b.0, a.1), *b, (a.0, b.1)];
+                for i in 0..4 {
+                    segments.push((corners[i], corners[(i + 1) % 4]));
+                }
+            }
+            Shape::Ellipse(a, b)   => {
+                let (cx, cy) = ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+                let (rx, ry) = ((b.0 - a.0).abs() / 2.0, (b.1 - a.1).abs() / 2.0);
+                let steps = 72;
+                let point = |i: usize| {
+                    let t = i as f32 / steps as f32 * 2.0 * PI;
+                    (cx + rx * t.cos(), cy + ry * t.sin())
+                };
+                for i in 0..steps {
+                    segments.push((point(i), point(i + 1)));
+                }
+            }
+            Shape::Pen(points)     => {
+                // A lone point is a dot, a stroke loaded without any is nothing
+                if let Some(first) = points.first() { segments.push((*first, *first)); }
+                for pair in points.windows(2) {
+                    segments.push((pair[0], pair[1]));
+                }
+            }
+            Shape::Redact(..)      => {}
+            Shape::Text(at, text)  => {
+                let scale = self.text_scale();
+                for (i, c) in text.chars().enumerate() {
+                    let glyph = BASIC_FONTS.get(c).or_else(|| LATIN_FONTS.get(c)).or_else(|| BASIC_FONTS.get('?')).unwrap();
+                    for (row, bits) in glyph.iter().enumerate() {
+                        for column in 0..8 {
+                            if bits & 1 << column != 0 {
+                                squares.push((at.0 + (i * 8 + column) as f32 * scale, at.1 + row as f32 * scale, scale));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        (segments, squares)
+    }
+}
+
+/* Draw `annotations` onto `image`, whose top left corner sits at `origin`
+ * in global physical pixels. Every shape is blended once so overlapping
+ * segments of a translucent stroke don't darken each other. Redactions
+ * are left to `redact::apply`.
+ */
+pub fn rasterize(image: &mut RgbaImage, annotations: &[Annotation], origin: Point) {
+    for annotation in annotations {
+        let (segments, squares) = annotation.outline();
+        let local = |p: Point| (p.0 - origin.0, p.1 - origin.1);
+        let segments: Vec<(Point, Point)> = segments.into_iter().map(|(a, b)| (local(a), local(b))).collect();
+        let squares: Vec<(f32, f32, f32)> = squares.into_iter().map(|(x, y, side)| {
+            let (x, y) = local((x, y));
+            (x, y, side)
+        }).collect();
+
+        // Only the shape's bounding box is worth a mask
+        let radius = annotation.width / 2.0;
+        let bounds = extent(&segments, &squares, radius + 1.0);
+        let mut mask = match Mask::new(bounds, image.width(), image.height()) {
+            Some(mask) => mask,
+            None       => continue,
+        };
+        for (a, b) in segments {
+            mask.segment(a, b, radius);
+        }
+        for (x, y, side) in squares {
+            mask.square(x, y, side);
+        }
+        mask.blend(image, annotation.color);
+    }
+}
+
+// The box around segments `reach` pixels thick and squares, inverted when there are neither.
+fn extent(segments: &[(Point, Point)], squares: &[(f32, f32, f32)], reach: f32) -> (f32, f32, f32, f32) {
+    let mut bounds = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    let mut extend = |x0: f32, y0: f32, x1: f32, y1: f32| {
+        bounds = (bounds.0.min(x0), bounds.1.min(y0), bounds.2.max(x1), bounds.3.max(y1));
+    };
+    for (a, b) in segments {
+        extend(a.0.min(b.0) - reach, a.1.min(b.1) - reach, a.0.max(b.0) + reach, a.1.max(b.1) + reach);
+    }
+    for (x, y, side) in squares {
+        extend(*x, *y, x + side, y + side);
+    }
+    bounds
+}
+
+// Distance from `p` to the closest point of the segment from `a` to `b`.
+fn distance(p: Point, a: Point, b: Point) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0) } else { 0.0 };
+    ((p.0 - a.0 - t * dx).powi(2) + (p.1 - a.1 - t * dy).powi(2)).sqrt()
+}
+
+// Coverage of one shape over part of the image, 0 to 1 per pixel.
+struct Mask {
+    left: i64,
+    top: i64,
+    width: i64,
+    height: i64,
+    coverage: Vec<f32>,
+}
+
+impl Mask {
+    // A mask over (left, top, right, bottom) clipped to the image, none when they don't overlap.
+    fn new(bounds: (f32, f32, f32, f32), width: u32, height: u32) -> Option<Mask> {
+        let left = (bounds.0.floor() as i64).max(0);
+        let top = (bounds.1.floor() as i64).max(0);
+        let right = (bounds.2.ceil() as i64).min(width as i64);
+        let bottom = (bounds.3.ceil() as i64).min(height as i64);
+        if right <= left || bottom <= top { return None; }
+        let (width, height) = (right - left, bottom - top);
+        Some(Mask { left, top, width, height, coverage: vec![0.0; (width * height) as usize] })
+    }
+
+    fn cell(&mut self, x: i64, y: i64) -> Option<&mut f32> {
+        let (x, y) = (x - self.left, y - self.top);
+        if x < 0 || y < 0 || x >= self.width || y >= self.height { return None; }
+        self.coverage.get_mut((y * self.width + x) as usize)
+    }
+
+    // Pixels within `radius` of the segment, with a pixel of antialiasing at the edge.
+    fn segment(&mut self, a: Point, b: Point, radius: f32) {
+        let reach = radius + 1.0;
+        let (left, right) = ((a.0.min(b.0) - reach).floor() as i64, (a.0.max(b.0) + reach).ceil() as i64);
+        let (top, bottom) = ((a.1.min(b.1) - reach).floor() as i64, (a.1.max(b.1) + reach).ceil() as i64);
+        for y in top.max(self.top)..bottom.min(self.top + self.height) {
+            for x in left.max(self.left)..right.min(self.left + self.width) {
+                let distance = distance((x as f32 + 0.5, y as f32 + 0.5), a, b);
+                let coverage = (radius + 0.5 - distance).clamp(0.0, 1.0);
+                if let Some(cell) = self.cell(x, y) { *cell = cell.max(coverage); }
+            }
+        }
+    }
+
+    fn square(&mut self, x: f32, y: f32, side: f32) {
+        let (left, top, side) = (x.round() as i64, y.round() as i64, side.round() as i64);
+        for y in top..top + side {
+            for x in left..left + side {
+                if let Some(cell) = self.cell(x, y) { *cell = 1.0; }
+            }
+        }
+    }
+
+    fn blend(&self, image: &mut RgbaImage, color: [u8; 4]) {
+        for (i, coverage) in self.coverage.iter().enumerate() {
+            if *coverage <= 0.0 { continue; }
+            let (x, y) = (self.left + i as i64 % self.width, self.top + i as i64 / self.width);
+            let pixel = image.get_pixel_mut(x as u32, y as u32);
+            // `color` over the pixel, either may be translucent
+            let alpha = coverage * color[3] as f32 / 255.0;
+            let below = pixel.0[3] as f32 / 255.0 * (1.0 - alpha);
+            let total = alpha + below;
+            for c in 0..3 {
+                pixel.0[c] = ((color[c] as f32 * alpha + pixel.0[c] as f32 * below) / total).round() as u8;
+            }
+            pixel.0[3] = (total * 255.0).round() as u8;
+        }
+    }
+}
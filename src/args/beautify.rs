@@ -0,0 +1,166 @@
+use image::{imageops, Rgba, RgbaImage};
+use log::warn;
+
+use super::config::{self, Config};
+use super::redact;
+
+// Height of the window chrome's title bar and its three buttons.
+const BAR: u32 = 28;
+const BUTTON_RADIUS: f32 = 6.0;
+const BUTTONS: [[u8; 4]; 3] = [
+    [0xfb, 0x49, 0x34, 0xff],
+    [0xfa, 0xbd, 0x2f, 0xff],
+    [0xb8, 0xbb, 0x26, 0xff],
+];
+const BAR_COLOR: [u8; 4] = [0x3c, 0x38, 0x36, 0xff];
+// How dark the shadow is right under the capture.
+const SHADOW_ALPHA: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    Transparent,
+    Solid([u8; 4]),
+    Gradient([u8; 4], [u8; 4]), // from the top left to the bottom right corner
+}
+
+impl Background {
+    // `none`, `#rrggbb` or `#rrggbb:#rrggbb` for a gradient, any color may have an alpha.
+    pub fn parse(value: &str) -> Option<Background> {
+        if value.trim().eq_ignore_ascii_case("none") { return Some(Background::Transparent); }
+        match value.split_once(':') {
+            Some((from, to)) => Some(Background::Gradient(config::color(from)?, config::color(to)?)),
+            None             => Some(Background::Solid(config::color(value)?)),
+        }
+    }
+
+    fn at(&self, x: u32, y: u32, width: u32, height: u32) -> [u8; 4] {
+        match *self {
+            Background::Transparent        => [0, 0, 0, 0],
+            Background::Solid(color)       => color,
+            Background::Gradient(from, to) => {
+                let t = (x as f32 / width.max(1) as f32 + y as f32 / height.max(1) as f32) / 2.0;
+                [0, 1, 2, 3].map(|c| (from[c] as f32 + (to[c] as f32 - from[c] as f32) * t).round() as u8)
+            }
+        }
+    }
+}
+
+// How a capture is dressed up for docs, all in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub padding: u32,
+    pub radius: u32,          // of the corners
+    pub shadow: u32,          // how far the shadow spreads, 0 for none
+    pub background: Background,
+    pub chrome: bool,         // a title bar with window buttons on top
+}
+
+impl Style {
+    // The defaults, overridden by `padding`, `radius`, `shadow`, `background` and `chrome` in the config.
+    pub fn load(config: &Config) -> Style {
+        let number = |key: &str, default: u32| match config.get(key) {
+            Some(value) => value.parse::<u32>().unwrap_or_else(|_| {
+                warn!("Invalid {} {}, expected pixels", key, value);
+                default
+            }),
+            None        => default,
+        };
+        Style {
+            padding: number("padding", 64),
+            radius: number("radius", 10),
+            shadow: number("shadow", 24),
+            background: config.get("background").and_then(Background::parse)
+                .unwrap_or(Background::Gradient([0x45, 0x85, 0x88, 0xff], [0xb1, 0x62, 0x86, 0xff])),
+            chrome: config.get("chrome") == Some("true"),
+        }
+    }
+
+    // Where the top left pixel of the capture ends up.
+    pub fn offset(&self) -> (i32, i32) {
+        let bar = if self.chrome { BAR } else { 0 };
+        (self.padding as i32, (self.padding + bar) as i32)
+    }
+}
+
+/* The capture with the window chrome on top, its corners rounded, on the
+ * background with `padding` around it and a soft shadow below. Whatever
+ * is transparent in it, or around it without a background, stays so.
+ */
+pub fn apply(image: &RgbaImage, style: &Style) -> RgbaImage {
+    let mut content = if style.chrome { chrome(image) } else { image.clone() };
+    round(&mut content, style.radius as f32);
+
+    let (width, height) = (content.width() + 2 * style.padding, content.height() + 2 * style.padding);
+    let mut canvas = RgbaImage::from_fn(width, height, |x, y| Rgba(style.background.at(x, y, width, height)));
+    if style.shadow > 0 {
+        imageops::overlay(&mut canvas, &shadow(&content, width, height, style), 0, 0);
+    }
+    imageops::overlay(&mut canvas, &content, style.padding as i64, style.padding as i64);
+    canvas
+}
+
+// A title bar with three buttons above the image.
+fn chrome(image: &RgbaImage) -> RgbaImage {
+    let mut framed = RgbaImage::from_pixel(image.width(), image.height() + BAR, Rgba(BAR_COLOR));
+    imageops::replace(&mut framed, image, 0, BAR as i64);
+    for (i, color) in BUTTONS.iter().enumerate() {
+        let (cx, cy) = (20.0 + i as f32 * 20.0, BAR as f32 / 2.0);
+        for y in 0..BAR {
+            for x in 0..(cx + BUTTON_RADIUS + 1.0).min(image.width() as f32) as u32 {
+                let distance = ((x as f32 + 0.5 - cx).powi(2) + (y as f32 + 0.5 - cy).powi(2)).sqrt();
+                let coverage = (BUTTON_RADIUS + 0.5 - distance).clamp(0.0, 1.0);
+                if coverage > 0.0 {
+                    let pixel = framed.get_pixel_mut(x, y);
+                    for c in 0..3 {
+                        pixel.0[c] = (color[c] as f32 * coverage + pixel.0[c] as f32 * (1.0 - coverage)).round() as u8;
+                    }
+                }
+            }
+        }
+    }
+    framed
+}
+
+// Fade out the pixels outside circles of `radius` in each corner, antialiased.
+fn round(image: &mut RgbaImage, radius: f32) {
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let radius = radius.min(width / 2.0).min(height / 2.0);
+    if radius <= 0.0 { return; }
+    let reach = radius.ceil() as u32;
+    let corners = [
+        (0, 0, radius, radius),
+        (image.width().saturating_sub(reach), 0, width - radius, radius),
+        (0, image.height().saturating_sub(reach), radius, height - radius),
+        (image.width().saturating_sub(reach), image.height().saturating_sub(reach), width - radius, height - radius),
+    ];
+    for (left, top, cx, cy) in corners {
+        for y in top..(top + reach).min(image.height()) {
+            for x in left..(left + reach).min(image.width()) {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                // Only the part beyond the circle's center is rounded
+                let (dx, dy) = (if (px - cx) * (cx - width / 2.0) > 0.0 { px - cx } else { 0.0 },
+                                if (py - cy) * (cy - height / 2.0) > 0.0 { py - cy } else { 0.0 });
+                let coverage = (radius + 0.5 - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0);
+                let pixel = image.get_pixel_mut(x, y);
+                pixel.0[3] = (pixel.0[3] as f32 * coverage).round() as u8;
+            }
+        }
+    }
+}
+
+// The content's silhouette in black, a little lower than it and blurred, on a canvas of its own.
+fn shadow(content: &RgbaImage, width: u32, height: u32, style: &Style) -> RgbaImage {
+    let (left, top) = (style.padding, style.padding + style.shadow / 3);
+    let mut pixels = vec![[0.0f32; 4]; (width * height) as usize];
+    for (x, y, pixel) in content.enumerate_pixels() {
+        let (x, y) = (x + left, y + top);
+        if x < width && y < height {
+            pixels[(y * width + x) as usize][3] = pixel.0[3] as f32 * SHADOW_ALPHA;
+        }
+    }
+    // Three passes of a third each spread about as far as `shadow`
+    redact::blur_pixels(&mut pixels, width as usize, height as usize, (style.shadow / 3).max(1) as usize);
+    RgbaImage::from_fn(width, height, |x, y| {
+        Rgba(pixels[(y * width + x) as usize].map(|channel| channel.round() as u8))
+    })
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use log::{info, warn};
+
+// Defaults read from `$XDG_CONFIG_HOME/pictura/config` (or `~/.config/pictura/config`).
+// One `key = value` pair per line, lines starting with `#` are comments. CLI flags always win.
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn load() -> Config {
+        let mut values = HashMap::new();
+        let path = match path() {
+            Some(path) => path,
+            None       => return Config { values },
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_)       => return Config { values },
+        };
+        info!("Config: {:?}", path);
+
+        for (n, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            match line.split_once('=') {
+                Some((key, value)) => {
+                    values.insert(key.trim().to_string(), value.trim().to_string());
+                }
+                None => warn!("{:?}:{}: expected `key = value`", path, n + 1),
+            }
+        }
+        Config { values }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| &value[..])
+    }
+
+    // Every `prefix.name = value` pair as (name, value), sorted by name.
+    pub fn prefixed(&self, prefix: &str) -> Vec<(String, String)> {
+        let mut pairs: Vec<(String, String)> = self.values.iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(prefix)?.strip_prefix('.')?.to_string(), value.clone())))
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    pub fn color(&self, key: &str) -> Option<[u8; 4]> {
+        color(self.get(key)?)
+    }
+
+    // `16:9` or a plain `1.777` as width / height.
+    pub fn ratio(&self, key: &str) -> Option<f32> {
+        let value = self.get(key)?;
+        let ratio = match value.split_once(':') {
+            Some((w, h)) => w.trim().parse::<f32>().ok()? / h.trim().parse::<f32>().ok()?,
+            None         => value.parse::<f32>().ok()?,
+        };
+        if ratio.is_finite() && ratio > 0.0 { Some(ratio) } else { None }
+    }
+
+    // Comma separated `name:WxH` or bare `WxH` sizes, the latter named after themselves.
+    pub fn presets(&self, key: &str) -> Vec<(String, u32, u32)> {
+        let value = match self.get(key) {
+            Some(value) => value,
+            None        => return Vec::new(),
+        };
+        value.split(',').map(str::trim).filter(|preset| !preset.is_empty()).filter_map(|preset| {
+            let (name, size) = preset.rsplit_once(':').unwrap_or((preset, preset));
+            let (width, height) = size.split_once('x').unwrap_or((size, ""));
+            match (width.trim().parse::<u32>(), height.trim().parse::<u32>()) {
+                (Ok(width), Ok(height)) if width > 0 && height > 0 => Some((name.trim().to_string(), width, height)),
+                _ => {
+                    warn!("Invalid preset {}, expected name:WxH", preset);
+                    None
+                }
+            }
+        }).collect()
+    }
+}
+
+// `#rrggbb` or `#rrggbbaa` as rgba.
+pub fn color(value: &str) -> Option<[u8; 4]> {
+    let hex = value.trim().trim_start_matches('#');
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    match hex.len() {
+        6 => Some([channel(0)?, channel(2)?, channel(4)?, 255]),
+        8 => Some([channel(0)?, channel(2)?, channel(4)?, channel(6)?]),
+        _ => None,
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir).join("pictura").join("config"));
+    }
+    env::var("HOME").ok().map(|home| PathBuf::from(home).join(".config").join("pictura").join("config"))
+}
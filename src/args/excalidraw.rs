@@ -0,0 +1,158 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{json, Value};
+
+use super::annotate::{Annotation, Shape};
+
+// Between captures laid out side by side in one scene.
+const GAP: f64 = 40.0;
+
+/* An `.excalidraw` scene with every png encoded image as an embedded image
+ * element, left to right, and its shapes, in image pixels, as native
+ * elements on top. Each image is grouped with its shapes so they move
+ * together on a board.
+ */
+pub fn scene(images: &[Vec<u8>], shapes: &[Vec<Annotation>]) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    let mut elements = Vec::new();
+    let mut files = serde_json::Map::new();
+    let mut x = 0.0;
+    for (k, png) in images.iter().enumerate() {
+        let (width, height) = match image::load_from_memory(png) {
+            Ok(image) => (image.width() as f64, image.height() as f64),
+            Err(_)    => continue,
+        };
+        let id = format!("capture-{}", k);
+        files.insert(id.clone(), json!({
+            "mimeType": "image/png",
+            "id": id,
+            "dataURL": format!("data:image/png;base64,{}", STANDARD.encode(png)),
+            "created": now,
+        }));
+
+        let mut image = element("image", &id, elements.len(), (x, 0.0), (width, height), now);
+        image["strokeColor"] = json!("transparent");
+        image["fileId"] = json!(id);
+        image["status"] = json!("saved");
+        image["scale"] = json!([1, 1]);
+        elements.push(image);
+
+        for annotation in shapes.get(k).into_iter().flatten() {
+            if let Some(shape) = native(annotation, (x, 0.0), elements.len(), &id, now) {
+                elements.push(shape);
+            }
+        }
+        x += width + GAP;
+    }
+
+    let scene = json!({
+        "type": "excalidraw",
+        "version": 2,
+        "source": "pictura",
+        "elements": elements,
+        "appState": { "viewBackgroundColor": "#ffffff", "gridSize": null },
+        "files": files,
+    });
+    serde_json::to_string_pretty(&scene).unwrap()
+}
+
+// The fields every element has, `n` keeps ids and seeds unique within the scene.
+fn element(kind: &str, group: &str, n: usize, (x, y): (f64, f64), (width, height): (f64, f64), now: u64) -> Value {
+    json!({
+        "type": kind,
+        "id": format!("{}-{}", kind, n),
+        "version": 1,
+        "versionNonce": n + 1,
+        "isDeleted": false,
+        "fillStyle": "solid",
+        "strokeWidth": 1,
+        "strokeStyle": "solid",
+        "roughness": 0,
+        "opacity": 100,
+        "angle": 0,
+        "x": x,
+        "y": y,
+        "strokeColor": "#1e1e1e",
+        "backgroundColor": "transparent",
+        "width": width,
+        "height": height,
+        "seed": n + 1,
+        "groupIds": [group],
+        "frameId": null,
+        "roundness": null,
+        "boundElements": [],
+        "updated": now,
+        "link": null,
+        "locked": false,
+    })
+}
+
+/* `annotation` as an excalidraw element offset by `at`. Redactions are
+ * already in the pixels and have none, neither do pen strokes without points.
+ */
+fn native(annotation: &Annotation, at: (f64, f64), n: usize, group: &str, now: u64) -> Option<Value> {
+    let point = |p: &(f32, f32)| (at.0 + p.0 as f64, at.1 + p.1 as f64);
+    // Points relative to the first, with the size of the box they span
+    let path = |points: Vec<(f64, f64)>| {
+        let (x0, y0) = points[0];
+        let relative: Vec<[f64; 2]> = points.iter().map(|(x, y)| [x - x0, y - y0]).collect();
+        let span = |axis: usize| {
+            let values = relative.iter().map(|p| p[axis]);
+            values.clone().fold(0.0f64, f64::max) - values.fold(0.0f64, f64::min)
+        };
+        ((x0, y0), (span(0), span(1)), relative)
+    };
+    let [red, green, blue, alpha] = annotation.color;
+
+    let mut value = match &annotation.shape {
+        Shape::Arrow(from, to) | Shape::Line(from, to) => {
+            let (origin, size, points) = path(vec![point(from), point(to)]);
+            let arrow = matches!(annotation.shape, Shape::Arrow(..));
+            let mut value = element(if arrow { "arrow" } else { "line" }, group, n, origin, size, now);
+            value["points"] = json!(points);
+            value["lastCommittedPoint"] = Value::Null;
+            value["startBinding"] = Value::Null;
+            value["endBinding"] = Value::Null;
+            value["startArrowhead"] = Value::Null;
+            value["endArrowhead"] = if arrow { json!("arrow") } else { Value::Null };
+            value
+        }
+        Shape::Pen(points) if points.is_empty() => return None,
+        Shape::Pen(points) => {
+            let (origin, size, points) = path(points.iter().map(point).collect());
+            let mut value = element("freedraw", group, n, origin, size, now);
+            value["points"] = json!(points);
+            value["pressures"] = json!([]);
+            value["simulatePressure"] = json!(true);
+            value["lastCommittedPoint"] = Value::Null;
+            value
+        }
+        Shape::Rectangle(a, b) | Shape::Ellipse(a, b) => {
+            let (a, b) = (point(a), point(b));
+            let kind = if matches!(annotation.shape, Shape::Rectangle(..)) { "rectangle" } else { "ellipse" };
+            element(kind, group, n, (a.0.min(b.0), a.1.min(b.1)), ((b.0 - a.0).abs(), (b.1 - a.1).abs()), now)
+        }
+        Shape::Text(top_left, text) => {
+            // Monospace at the height of the bitmap font
+            let size = 8.0 * annotation.text_scale() as f64;
+            let extent = (text.chars().count() as f64 * size * 0.6, size * 1.25);
+            let mut value = element("text", group, n, point(top_left), extent, now);
+            value["text"] = json!(text);
+            value["originalText"] = json!(text);
+            value["fontSize"] = json!(size);
+            value["fontFamily"] = json!(3);
+            value["textAlign"] = json!("left");
+            value["verticalAlign"] = json!("top");
+            value["containerId"] = Value::Null;
+            value["lineHeight"] = json!(1.25);
+            value["baseline"] = json!(size.round());
+            value
+        }
+        Shape::Redact(..) => return None,
+    };
+    value["strokeColor"] = json!(format!("#{:02x}{:02x}{:02x}", red, green, blue));
+    value["opacity"] = json!((alpha as f64 / 255.0 * 100.0).round());
+    value["strokeWidth"] = json!(annotation.width);
+    Some(value)
+}
@@ -1,6 +1,11 @@
-use screenshots::{Compression, Screen};
+use screenshots::Screen;
+use image::{ImageEncoder, RgbaImage};
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use std::io::{self, Read};
 use log::{info, warn, error};
 
+use super::{annotate, redact, scroll, trim};
+
 
 // Struct for pixels on the screen.
 pub struct Point {
@@ -22,20 +27,30 @@ impl Point {
         }
     }
 }
+
+impl Rectangle {
+    // The part of it on `image`, which has its top left corner at `origin`. None when it is entirely off it.
+    pub fn within(&self, image: &RgbaImage, origin: &Point) -> Option<Rectangle> {
+        let tl = Point { x: self.tl.x.max(origin.x), y: self.tl.y.max(origin.y) };
+        let br = Point { x: self.br.x.min(origin.x + image.width() as i32), y: self.br.y.min(origin.y + image.height() as i32) };
+        if tl.x < br.x && tl.y < br.y { Some(Rectangle { tl, br }) } else { None }
+    }
+}
 /* TODO: 
  *  DONE: - grab screens from point
  *  TODO: - attempt to allow a square of 2 monitors
  *  TODO: - attempt to allow a square of 3+ monitors
  *  DONE: - make point order and position ambigious (i.e. (bl,tr), (tl,br), etc)
  */
-fn screenshot(global_coordinates: (Option<Point>, Option<Point>)) -> Vec<screenshots::Image> {
+// Every image comes with the global position of its top left corner.
+fn screenshot(global_coordinates: (Option<Point>, Option<Point>)) -> Vec<(screenshots::Image, Point)> {
     if global_coordinates.0.is_none() && global_coordinates.1.is_none() {
     //if screen.is_none() {
         let screens = Screen::all().unwrap();
         let mut images = Vec::new();
         for capture in screens {
             let cap = capture.capture().unwrap();
-            images.push(cap); 
+            images.push((cap, Point { x: capture.display_info.x, y: capture.display_info.y })); 
             info!("{capture:?}");
         }
         images
@@ -58,13 +73,15 @@ fn screenshot(global_coordinates: (Option<Point>, Option<Point>)) -> Vec<screens
         };
 
 
-        let screen_tl = Screen::from_point(global_tl.x, global_tl.y).unwrap();   // Screen that contains the top left coodinate
-        let screen_br = Screen::from_point(global_br.x, global_br.y).unwrap();   // Screen that contains the bottom right coordinate
-        let mut local_tl  = global_tl.to_local(screen_tl);                  // Top left in local coordinates
-        let mut local_br  = global_br.to_local(screen_br);                  // Bottom right in local coordinates
+        // Both corners on one screen, a corner on none (past an edge or between screens) is
+        // handled like a rectangle spanning several
+        let single = match (Screen::from_point(global_tl.x, global_tl.y), Screen::from_point(global_br.x, global_br.y)) {
+            (Ok(screen_tl), Ok(screen_br)) if screen_tl.display_info.id == screen_br.display_info.id => Some(screen_tl),
+            _                                                                                        => None,
+        };
             
-        println!("This should print no matter what");
-        if screen_tl.display_info.id != screen_br.display_info.id {
+        info!("This should print no matter what");
+        if single.is_none() {
             /* TODO:
              *  TODO: - top to bottom
              *  - test left to right
@@ -72,21 +89,23 @@ fn screenshot(global_coordinates: (Option<Point>, Option<Point>)) -> Vec<screens
              *  TODO: - cover middle screen
              */
                 
-            println!("This should print if the screens are different");
-            let mut images = Vec::<screenshots::Image>::new();
+            info!("This should print if the screens are different");
+            let mut images = Vec::<(screenshots::Image, Point)>::new();
             for screen in Screen::all().unwrap() {
                 if do_overlap(Point { x: screen.display_info.x, 
                                       y: screen.display_info.y }, &global_tl, 
                               Point { x: screen.display_info.x + screen.display_info.width as i32,
                                       y: screen.display_info.y + screen.display_info.height as i32}, &global_br) {
-                    println!("This should print if the screens overlap");
-                    local_tl = global_tl.to_local(screen);
-                    local_br = global_br.to_local(screen);
+                    info!("This should print if the screens overlap");
+                    let local_tl = global_tl.to_local(screen);
+                    let local_br = global_br.to_local(screen);
                     info!("local_tl: {} {}\nlocal_br: {} {}", local_tl.x, local_tl.y, local_br.x, local_br.y);
-                    let cap = screen.capture_area(local_tl.x, local_tl.y, 
-                                                  (local_br.x - local_tl.x) as u32, 
-                                                  (local_br.y - local_tl.y) as u32).unwrap();
-                    images.push(cap);
+                    match screen.capture_area(local_tl.x, local_tl.y, 
+                                              (local_br.x - local_tl.x) as u32, 
+                                              (local_br.y - local_tl.y) as u32) {
+                        Ok(cap) => images.push((cap, Point { x: screen.display_info.x + local_tl.x, y: screen.display_info.y + local_tl.y })),
+                        Err(e)  => error!("Could not capture screen {}: {}", screen.display_info.id, e),
+                    }
                 }
             }
             images
@@ -138,55 +157,271 @@ fn screenshot(global_coordinates: (Option<Point>, Option<Point>)) -> Vec<screens
             /* DONE:
              *  DONE: - convert global to local
              */
+            let screen_tl = single.unwrap();
+            let local_tl  = global_tl.to_local(screen_tl);                  // Top left in local coordinates
+            let local_br  = global_br.to_local(screen_tl);                  // Bottom right in local coordinates
             let width:u32  = (local_br.x - local_tl.x) as u32;
             let height:u32 = (local_br.y - local_tl.y) as u32;
             info!("LOCAL_TL: {} / {}\nLOCAL_BR {} / {}\nRESULT: {width} / {height}\n{screen_tl:?}", local_tl.x, local_tl.y, local_br.x, local_br.y);
-            vec![screen_tl.capture_area(local_tl.x, local_tl.y, width, height).unwrap()]
+            vec![(screen_tl.capture_area(local_tl.x, local_tl.y, width, height).unwrap(),
+                  Point { x: screen_tl.display_info.x + local_tl.x, y: screen_tl.display_info.y + local_tl.y })]
         }
 
     }
 
     }
 
+    /* The part of `width` by `height` at global `x`, `y` that lies within
+     * the bounding box of all screens, as its top left and bottom right
+     * corners. None when it is entirely off them.
+     */
+    pub fn on_screens(x: i32, y: i32, width: u32, height: u32) -> Option<(Point, Point)> {
+        let screens = Screen::all().ok()?;
+        let left   = screens.iter().map(|s| s.display_info.x).min()?;
+        let top    = screens.iter().map(|s| s.display_info.y).min()?;
+        let right  = screens.iter().map(|s| s.display_info.x + s.display_info.width as i32).max()?;
+        let bottom = screens.iter().map(|s| s.display_info.y + s.display_info.height as i32).max()?;
+        let tl = Point { x: x.max(left), y: y.max(top) };
+        let br = Point { x: (x + width as i32).min(right), y: (y + height as i32).min(bottom) };
+        if tl.x < br.x && tl.y < br.y { Some((tl, br)) } else { None }
+    }
+
+    // Redactions are applied to the raw pixels, nothing unredacted ever gets encoded.
+    // With `auto` the words its detectors find are redacted as well, with
+    // `trim` uniform margins within that tolerance are cut off afterwards.
     pub fn run(compression: Option<String>,
-               bounds: (Option<Point>, Option<Point>)) 
+               bounds: (Option<Point>, Option<Point>),
+               redactions: &[redact::Redaction],
+               auto: Option<&redact::Auto>,
+               trim: Option<u8>) 
         -> Vec<Vec<u8>> {
 
-            let images = screenshot((bounds.0, bounds.1));
-            let mut compressed_buffers = Vec::new();
-            for image in images {
-                match &*compression.clone().unwrap_or_default().to_lowercase() {
-                    "Best"  => {
-                        compressed_buffers.push(image.to_png(Compression::Best).unwrap());
-                    },
-                    "Fast"  => {
-                        compressed_buffers.push(image.to_png(Compression::Fast).unwrap());
-                    },
-
-                    _   => {
-                        compressed_buffers.push(image.to_png(Compression::Default).unwrap());
+            let images = screenshot((bounds.0, bounds.1))
+                .iter()
+                .map(|(image, origin)| {
+                    let image = RgbaImage::from_raw(image.width(), image.height(), image.rgba().clone()).unwrap();
+                    let image = redacted(image, origin, redactions, auto);
+                    match trim {
+                        Some(tolerance) => trimmed(image, tolerance, |x, y, width, height| {
+                            // Global, so the same region can be captured again right away
+                            let (x, y) = (origin.x + x as i32, origin.y + y as i32);
+                            println!("Trimmed to {},{} {}x{}, reuse with --output {} {} {} {}",
+                                     x, y, width, height, x, y, x + width as i32, y + height as i32);
+                        }),
+                        None            => image,
                     }
+                })
+                .collect();
+            encode(images, compression)
+        }
+
+    // Every screen pasted into one image spanning `tl` to `br`, the frozen frame behind the overlay.
+    pub fn frame(tl: &Point, br: &Point) -> RgbaImage {
+        let mut frame = RgbaImage::new((br.x - tl.x) as u32, (br.y - tl.y) as u32);
+        for screen in Screen::all().unwrap() {
+            let cap = screen.capture().unwrap();
+            let image = RgbaImage::from_raw(cap.width(), cap.height(), cap.rgba().clone()).unwrap();
+            image::imageops::overlay(&mut frame, &image,
+                                     (screen.display_info.x - tl.x) as i64,
+                                     (screen.display_info.y - tl.y) as i64);
+        }
+        frame
+    }
+
+    /* Several regions cut out of `frozen`, the screens with their top left
+     * corner at the given point, or out of a fresh capture without one.
+     * The annotations are redacted and drawn into the screens first. Each
+     * becomes its own image or, with `composite`, they are pasted at their
+     * relative positions into one image of their bounding box that is
+     * transparent between them.
+     */
+    pub fn run_regions(compression: Option<String>,
+                       frozen: Option<(&RgbaImage, &Point)>,
+                       regions: &[Rectangle],
+                       annotations: &[annotate::Annotation],
+                       composite: bool)
+        -> Vec<Vec<u8>> {
+
+            if regions.is_empty() { return Vec::new(); }
+            let tl = Point { x: regions.iter().map(|r| r.tl.x).min().unwrap(),
+                             y: regions.iter().map(|r| r.tl.y).min().unwrap() };
+            let br = Point { x: regions.iter().map(|r| r.br.x).max().unwrap(),
+                             y: regions.iter().map(|r| r.br.y).max().unwrap() };
+            let captured;
+            let (screens, origin) = match frozen {
+                Some(frozen) => frozen,
+                None         => {
+                    captured = frame(&tl, &br);
+                    (&captured, &tl)
+                }
+            };
+            let annotated;
+            let screens = if annotations.is_empty() { screens } else {
+                annotated = {
+                    let mut screens = screens.clone();
+                    let redactions: Vec<redact::Redaction> = annotations.iter().filter_map(|a| a.redaction()).collect();
+                    redact::apply(&mut screens, &redactions, (origin.x, origin.y));
+                    annotate::rasterize(&mut screens, annotations, (origin.x as f32, origin.y as f32));
+                    screens
+                };
+                &annotated
+            };
+
+            // Only what of each region is on the frame, at its own place
+            let crops = regions.iter().filter_map(|r| {
+                let (left, top, right, bottom) = match r.within(screens, origin) {
+                    Some(on) => (on.tl.x, on.tl.y, on.br.x, on.br.y),
+                    None     => {
+                        warn!("Region {},{} to {},{} is off the screens", r.tl.x, r.tl.y, r.br.x, r.br.y);
+                        return None;
+                    }
+                };
+                let image = image::imageops::crop_imm(screens,
+                                                      (left - origin.x) as u32,
+                                                      (top - origin.y) as u32,
+                                                      (right - left) as u32,
+                                                      (bottom - top) as u32).to_image();
+                Some((left - tl.x, top - tl.y, image))
+            });
+            let images = if composite {
+                let mut canvas = RgbaImage::new((br.x - tl.x) as u32, (br.y - tl.y) as u32);
+                for (x, y, image) in crops {
+                    image::imageops::replace(&mut canvas, &image, x as i64, y as i64);
                 }
+                vec![canvas]
+            } else {
+                crops.map(|(_, _, image)| image).collect()
+            };
+            encode(images, compression)
+        }
+
+    // Same as `run` but for image files instead of the screen, `-` reads stdin.
+    // Redactions are relative to the top left corner of every image.
+    pub fn run_input(compression: Option<String>,
+                     inputs: &[String],
+                     redactions: &[redact::Redaction],
+                     auto: Option<&redact::Auto>,
+                     trim: Option<u8>)
+        -> Vec<Vec<u8>> {
+
+            let mut images = Vec::new();
+            for input in inputs {
+                match load(input) {
+                    Ok(image) => {
+                        let mut image = redacted(image, &Point { x: 0, y: 0 }, redactions, auto);
+                        if let Some(tolerance) = trim {
+                            image = trimmed(image, tolerance, |x, y, width, height| {
+                                println!("Trimmed {} to {},{} {}x{}", input, x, y, width, height);
+                            });
+                        }
+                        images.push(image);
+                    }
+                    Err(e)   => error!("Could not read {}: {}", input, e),
+                }
+            }
+            encode(images, compression)
+        }
+
+    /* Frames of a scrolling capture stitched into one image, redacted and
+     * trimmed like `run`. Redactions are relative to `origin`, the region's
+     * top left, as if the page went on below it.
+     */
+    pub fn run_stitched(compression: Option<String>,
+                        frames: &[RgbaImage],
+                        origin: &Point,
+                        redactions: &[redact::Redaction],
+                        auto: Option<&redact::Auto>,
+                        trim: Option<u8>)
+        -> Vec<Vec<u8>> {
+
+            let image = match scroll::stitch(frames) {
+                Some(image) => redacted(image, origin, redactions, auto),
+                None        => return Vec::new(),
+            };
+            println!("Stitched {} frames into {}x{}", frames.len(), image.width(), image.height());
+            let image = match trim {
+                Some(tolerance) => trimmed(image, tolerance, |x, y, width, height| {
+                    println!("Trimmed to {},{} {}x{}", x, y, width, height);
+                }),
+                None            => image,
+            };
+            encode(vec![image], compression)
+        }
+
+    // With `auto` the words its detectors find are redacted as well.
+    fn redacted(mut image: RgbaImage, origin: &Point, redactions: &[redact::Redaction], auto: Option<&redact::Auto>) -> RgbaImage {
+        redact::apply(&mut image, redactions, (origin.x, origin.y));
+        if let Some(auto) = auto {
+            let found = redact::detect(&image, (origin.x, origin.y), auto);
+            redact::apply(&mut image, &found, (origin.x, origin.y));
+        }
+        image
+    }
+
+    // `image` without its uniform margins, `report` gets what is left of it.
+    fn trimmed(image: RgbaImage, tolerance: u8, report: impl FnOnce(u32, u32, u32, u32)) -> RgbaImage {
+        match trim::bounds(&image, tolerance) {
+            Some((x, y, width, height)) => {
+                report(x, y, width, height);
+                image::imageops::crop_imm(&image, x, y, width, height).to_image()
             }
-            compressed_buffers
+            None                        => {
+                warn!("Nothing but margins to trim, keeping the whole image");
+                image
+            }
+        }
+    }
+
+    pub fn load(input: &str) -> Result<RgbaImage, image::ImageError> {
+        if input == "-" {
+            let mut buffer = Vec::new();
+            io::stdin().read_to_end(&mut buffer)?;
+            return Ok(image::load_from_memory(&buffer)?.to_rgba8());
         }
+        Ok(image::open(input)?.to_rgba8())
+    }
+
+    // Decode every png encoded image, change it and encode it again.
+    pub fn transform(images: Vec<Vec<u8>>, mut change: impl FnMut(usize, RgbaImage) -> RgbaImage) -> Vec<Vec<u8>> {
+        let images = images.iter().enumerate().map(|(k, png)| {
+            change(k, image::load_from_memory(png).unwrap().to_rgba8())
+        }).collect();
+        encode(images, None)
+    }
+
+    pub fn encode(images: Vec<RgbaImage>, compression: Option<String>) -> Vec<Vec<u8>> {
+        let compression = match &*compression.unwrap_or_default().to_lowercase() {
+            "best" => CompressionType::Best,
+            "fast" => CompressionType::Fast,
+            _      => CompressionType::Default,
+        };
+        let mut compressed_buffers = Vec::new();
+        for image in images {
+            let mut buffer = Vec::new();
+            PngEncoder::new_with_quality(&mut buffer, compression, FilterType::Adaptive)
+                .write_image(image.as_raw(), image.width(), image.height(), image::ColorType::Rgba8)
+                .unwrap();
+            compressed_buffers.push(buffer);
+        }
+        compressed_buffers
+    }
 
     fn do_overlap(l1: Point, l2: &Point, r1: Point, r2: &Point) -> bool {
-        println!("l1: {} {}\nr1: {} {}\nl2: {} {}\nr2: {} {}", l1.x, l1.y, r1.x, r1.y, l2.x, l2.y, r2.x, r2.y);
+        info!("l1: {} {}\nr1: {} {}\nl2: {} {}\nr2: {} {}", l1.x, l1.y, r1.x, r1.y, l2.x, l2.y, r2.x, r2.y);
         // if rectangle has area 0, no overlap
         if l1.x == r1.x || l1.y == r1.y || r2.x == l2.x || l2.y == r2.y {
-            println!("condition 1");
+            info!("condition 1");
             return false;
         }
-        // If one rectangle is on left side of other
-        if l1.x > r2.x || l2.x > r1.x {
-            println!("condition 2");
+        // If one rectangle is on left side of other, or only touches it
+        if l1.x >= r2.x || l2.x >= r1.x {
+            info!("condition 2");
             return false;
         }
 
-        // If one rectangle is above other
-        if r1.y < l2.y || r2.y < l1.y {
-            println!("condition 3");
+        // If one rectangle is above other, or only touches it
+        if r1.y <= l2.y || r2.y <= l1.y {
+            info!("condition 3");
             return false;
         }
 
@@ -1 +1,277 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use leptess::{LepTess, Variable};
+use log::{info, warn};
+pub mod layout;
+mod table;
 
+pub const DEFAULT_LANG: &str = "eng";
+
+// Places tesseract installs its `*.traineddata` models when TESSDATA_PREFIX isn't set.
+const TESSDATA_DIRS: [&str; 6] = [
+    "/usr/share/tesseract-ocr/5/tessdata",
+    "/usr/share/tesseract-ocr/4.00/tessdata",
+    "/usr/share/tessdata",
+    "/usr/local/share/tessdata",
+    "/opt/homebrew/share/tessdata",
+    "/usr/local/share/tesseract-ocr/tessdata",
+];
+
+// Tesseract page segmentation modes worth exposing, see `tesseract --help-psm`.
+#[derive(Debug, Clone, Copy)]
+pub enum Psm {
+    Auto,   // full windows / screens, no assumption about layout
+    Column, // a single column of text of variable sizes
+    Block,  // a single uniform block of text
+    Line,   // a single line, e.g. a title bar or a terminal prompt
+    Word,   // a single word
+    Sparse, // as much text as possible in no particular order
+}
+
+impl Psm {
+    pub fn parse(name: &str) -> Option<Psm> {
+        match &*name.to_lowercase() {
+            "auto"   | "3"  => Some(Psm::Auto),
+            "column" | "4"  => Some(Psm::Column),
+            "block"  | "6"  => Some(Psm::Block),
+            "line"   | "7"  => Some(Psm::Line),
+            "word"   | "8"  => Some(Psm::Word),
+            "sparse" | "11" => Some(Psm::Sparse),
+            _               => None,
+        }
+    }
+
+    fn value(&self) -> &'static str {
+        match self {
+            Psm::Auto   => "3",
+            Psm::Column => "4",
+            Psm::Block  => "6",
+            Psm::Line   => "7",
+            Psm::Word   => "8",
+            Psm::Sparse => "11",
+        }
+    }
+}
+
+// How recognized words are put back together into text.
+#[derive(Debug, Clone, Copy)]
+pub enum Layout {
+    Flow,     // tesseract's own reading order, whitespace collapsed
+    Preserve, // leading whitespace and column alignment rebuilt from word boxes
+}
+
+impl Layout {
+    pub fn parse(name: &str) -> Option<Layout> {
+        match &*name.to_lowercase() {
+            "flow"     | "none" => Some(Layout::Flow),
+            "preserve"          => Some(Layout::Preserve),
+            _                   => None,
+        }
+    }
+}
+
+// Output formats of the table mode.
+#[derive(Debug, Clone, Copy)]
+pub enum TableFormat {
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+impl TableFormat {
+    pub fn parse(name: &str) -> Option<TableFormat> {
+        match &*name.to_lowercase() {
+            "csv"              => Some(TableFormat::Csv),
+            "tsv"              => Some(TableFormat::Tsv),
+            "markdown" | "md"  => Some(TableFormat::Markdown),
+            _                  => None,
+        }
+    }
+}
+
+pub struct Options {
+    pub lang: String, // one or more models joined by `+`, e.g. `eng+deu+jpn`
+    pub psm: Psm,
+    pub layout: Layout,
+    pub table: Option<TableFormat>, // emit the capture as a table instead of text
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options { lang: DEFAULT_LANG.to_string(), psm: Psm::Auto, layout: Layout::Flow, table: None }
+    }
+}
+
+// A recognized word and its bounding box in image pixels.
+#[derive(Debug, Clone)]
+pub struct Word {
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    pub conf: f32,
+    pub text: String,
+}
+
+pub fn tessdata_dir() -> Option<PathBuf> {
+    if let Ok(prefix) = env::var("TESSDATA_PREFIX") {
+        let prefix = PathBuf::from(prefix);
+        // Both `.../tessdata` and its parent are accepted by tesseract
+        if prefix.join("tessdata").is_dir() {
+            return Some(prefix.join("tessdata"));
+        }
+        return Some(prefix);
+    }
+    TESSDATA_DIRS.iter().map(PathBuf::from).find(|dir| dir.is_dir())
+}
+
+// Languages the backend can load, i.e. every `<lang>.traineddata` in the tessdata dir.
+pub fn available_langs() -> Vec<String> {
+    let mut langs = Vec::new();
+    let dir = match tessdata_dir() {
+        Some(dir) => dir,
+        None      => return langs,
+    };
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "traineddata") {
+                if let Some(stem) = path.file_stem() {
+                    // osd is the orientation/script model, not something to recognize text with
+                    if stem != "osd" { langs.push(stem.to_string_lossy().into_owned()); }
+                }
+            }
+        }
+    }
+    langs.sort();
+    langs
+}
+
+pub fn validate_langs(lang: &str) -> Result<(), String> {
+    let available = available_langs();
+    if available.is_empty() {
+        return Err(String::from("no tesseract models found, set TESSDATA_PREFIX"));
+    }
+    let missing: Vec<&str> = lang
+        .split('+')
+        .filter(|l| l.is_empty() || !available.iter().any(|a| a == l))
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("unknown language(s) {:?}, available: {}", missing, available.join(" ")))
+    }
+}
+
+fn init(options: &Options) -> Option<LepTess> {
+    let dir = tessdata_dir();
+    let mut tess = match LepTess::new(dir.as_ref().and_then(|d| d.to_str()), &options.lang) {
+        Ok(tess) => tess,
+        Err(e)   => {
+            warn!("Could not load tesseract with {}: {}", options.lang, e);
+            return None;
+        }
+    };
+    if tess.set_variable(Variable::TesseditPagesegMode, options.psm.value()).is_err() {
+        warn!("Could not set page segmentation mode {:?}", options.psm);
+    }
+    Some(tess)
+}
+
+// Extract the text of every png encoded buffer, one String per image.
+pub fn run(images: &[Vec<u8>], options: &Options) -> Vec<String> {
+    if let Some(format) = options.table {
+        return tables(images, options, format);
+    }
+    if let Layout::Preserve = options.layout {
+        return words(images, options).iter().map(|words| layout::preserve(words)).collect();
+    }
+
+    info!("OCR lang: {} psm: {:?}", options.lang, options.psm);
+    let mut tess = match init(options) {
+        Some(tess) => tess,
+        None       => return Vec::new(),
+    };
+
+    let mut texts = Vec::new();
+    for image in images {
+        if tess.set_image_from_mem(image).is_err() {
+            warn!("Could not read image for OCR");
+            texts.push(String::new());
+            continue;
+        }
+        texts.push(tess.get_utf8_text().unwrap_or_default());
+    }
+    texts
+}
+
+// Extract a table from every png encoded buffer, formatted as csv, tsv or markdown.
+pub fn tables(images: &[Vec<u8>], options: &Options, format: TableFormat) -> Vec<String> {
+    let words = words(images, options);
+    images
+        .iter()
+        .zip(words.iter())
+        .map(|(image, words)| match image::load_from_memory(image) {
+            Ok(image) => table::format(&table::extract(&image.to_luma8(), words), format),
+            Err(e)    => {
+                warn!("Could not decode image for table extraction: {}", e);
+                String::new()
+            }
+        })
+        .collect()
+}
+
+// Recognize the words of every png encoded buffer together with their boxes.
+pub fn words(images: &[Vec<u8>], options: &Options) -> Vec<Vec<Word>> {
+    info!("OCR words lang: {} psm: {:?}", options.lang, options.psm);
+    let mut tess = match init(options) {
+        Some(tess) => tess,
+        None       => return Vec::new(),
+    };
+
+    let mut words = Vec::new();
+    for image in images {
+        if tess.set_image_from_mem(image).is_err() {
+            warn!("Could not read image for OCR");
+            words.push(Vec::new());
+            continue;
+        }
+        words.push(parse_tsv(&tess.get_tsv_text(0).unwrap_or_default()));
+    }
+    words
+}
+
+/* Tesseract's TSV has one row per page, block, paragraph, line and word:
+ *  level page_num block_num par_num line_num word_num left top width height conf text
+ * only the word rows (level 5) carry text.
+ */
+fn parse_tsv(tsv: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    for row in tsv.lines() {
+        let fields: Vec<&str> = row.split('\t').collect();
+        if fields.len() < 12 || fields[0] != "5" { continue; }
+        let text = fields[11..].join("\t");
+        if text.trim().is_empty() { continue; }
+        let number = |i: usize| fields[i].trim().parse::<i32>().unwrap_or(0);
+        words.push(Word {
+            left: number(6),
+            top: number(7),
+            width: number(8),
+            height: number(9),
+            conf: fields[10].trim().parse::<f32>().unwrap_or(-1.0),
+            text: text.trim().to_string(),
+        });
+    }
+    words
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::Word;
+
+    // A word boxed at `left`, `top` the way tesseract would, 10 pixels per character.
+    pub fn word(left: i32, top: i32, text: &str) -> Word {
+        Word { left, top, width: text.chars().count() as i32 * 10, height: 12, conf: 90.0, text: text.to_string() }
+    }
+}
@@ -0,0 +1,144 @@
+use super::Word;
+
+/* Rebuild monospace text from word boxes:
+ *  - the cell width is estimated from the median advance per character
+ *  - every word is placed at the column its left edge falls on, relative
+ *    to the leftmost word, so indentation and aligned columns survive
+ *  - gaps between rows larger than the usual line pitch become blank lines
+ */
+pub fn preserve(words: &[Word]) -> String {
+    let rows = rows(words);
+    if rows.is_empty() { return String::new(); }
+
+    let cell = cell_width(words);
+    let origin = words.iter().map(|w| w.left).min().unwrap_or(0);
+    let pitch = line_pitch(&rows);
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut previous_top: Option<i32> = None;
+    for row in &rows {
+        let top = row_top(row);
+        if let Some(previous_top) = previous_top {
+            let skipped = ((top - previous_top) as f32 / pitch).round() as i32 - 1;
+            for _ in 0..skipped.max(0) {
+                lines.push(String::new());
+            }
+        }
+        previous_top = Some(top);
+
+        let mut line = String::new();
+        let mut length = 0;
+        for word in row {
+            let mut column = ((word.left - origin) as f32 / cell).round() as usize;
+            // Never glue two words together, even if the boxes overlap
+            if length > 0 && column <= length { column = length + 1; }
+            line.push_str(&" ".repeat(column - length));
+            line.push_str(&word.text);
+            length = column + word.text.chars().count();
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+// Group words into visual rows, top to bottom, each row sorted left to right.
+pub fn rows(words: &[Word]) -> Vec<Vec<&Word>> {
+    let mut sorted: Vec<&Word> = words.iter().collect();
+    sorted.sort_by_key(|w| w.top + w.height / 2);
+    let height = median(words.iter().map(|w| w.height as f32).collect()).unwrap_or(1.0);
+
+    let mut rows: Vec<Vec<&Word>> = Vec::new();
+    let mut center = f32::MIN;
+    for word in sorted {
+        let word_center = (word.top + word.height / 2) as f32;
+        match rows.last_mut() {
+            Some(row) if word_center - center < height / 2.0 => row.push(word),
+            _ => {
+                rows.push(vec![word]);
+                center = word_center;
+            }
+        }
+    }
+    for row in rows.iter_mut() {
+        row.sort_by_key(|w| w.left);
+    }
+    rows
+}
+
+// Width of one monospace cell in pixels.
+pub fn cell_width(words: &[Word]) -> f32 {
+    let advances = words
+        .iter()
+        .filter(|w| !w.text.is_empty())
+        .map(|w| w.width as f32 / w.text.chars().count() as f32)
+        .collect();
+    median(advances).unwrap_or(1.0).max(1.0)
+}
+
+fn line_pitch(rows: &[Vec<&Word>]) -> f32 {
+    let gaps = rows
+        .windows(2)
+        .map(|pair| (row_top(&pair[1]) - row_top(&pair[0])) as f32)
+        .collect();
+    let heights = rows.iter().flatten().map(|w| w.height as f32).collect();
+    median(gaps).or(median(heights)).unwrap_or(1.0).max(1.0)
+}
+
+fn row_top(row: &[&Word]) -> i32 {
+    row.iter().map(|w| w.top).min().unwrap_or(0)
+}
+
+fn median(mut values: Vec<f32>) -> Option<f32> {
+    if values.is_empty() { return None; }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Some(values[(values.len() - 1) / 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::word;
+
+    #[test]
+    fn nothing_recognized() {
+        assert_eq!(preserve(&[]), "");
+    }
+
+    #[test]
+    fn keeps_indentation() {
+        let words = [
+            word(100, 0, "fn"), word(130, 0, "main()"),
+            word(140, 20, "let"), word(180, 20, "x"),
+            word(100, 40, "}"),
+        ];
+        assert_eq!(preserve(&words), "fn main()\n    let x\n}");
+    }
+
+    #[test]
+    fn keeps_columns_aligned() {
+        let words = [
+            word(0, 0, "name"), word(100, 0, "size"),
+            word(0, 20, "a.txt"), word(100, 20, "12"),
+        ];
+        assert_eq!(preserve(&words), "name      size\na.txt     12");
+    }
+
+    #[test]
+    fn wide_gaps_between_rows_are_blank_lines() {
+        let words = [word(0, 0, "one"), word(0, 20, "two"), word(0, 40, "three"), word(0, 100, "four")];
+        assert_eq!(preserve(&words), "one\ntwo\nthree\n\n\nfour");
+    }
+
+    #[test]
+    fn overlapping_words_stay_apart() {
+        let words = [word(0, 0, "foo"), word(25, 0, "bar")];
+        assert_eq!(preserve(&words), "foo bar");
+    }
+
+    #[test]
+    fn rows_take_words_a_little_off_the_line() {
+        let words = [word(60, 3, "world"), word(0, 0, "hello"), word(0, 20, "next")];
+        let rows: Vec<Vec<&str>> = rows(&words).iter().map(|row| row.iter().map(|w| &w.text[..]).collect()).collect();
+        assert_eq!(rows, [vec!["hello", "world"], vec!["next"]]);
+    }
+}
@@ -0,0 +1,241 @@
+use image::GrayImage;
+use super::{layout, TableFormat, Word};
+
+// A ruling line has to span at least this share of the image to count as grid.
+const LINE_COVERAGE: f32 = 0.5;
+// Luminance difference from the background that makes a pixel part of a line.
+const LINE_CONTRAST: i16 = 48;
+
+/* Cells of the table in a captured image:
+ *  - if the image has ruling lines, they give the column and row boundaries
+ *  - otherwise columns are separated by vertical gaps no word crosses and
+ *    rows are the visual text rows
+ * every word lands in the cell containing its center.
+ */
+pub fn extract(image: &GrayImage, words: &[Word]) -> Vec<Vec<String>> {
+    if words.is_empty() { return Vec::new(); }
+
+    let (vertical, horizontal) = ruling_lines(image);
+    let columns = if vertical.len() >= 2 { vertical } else { column_gaps(words) };
+
+    let mut cells: Vec<Vec<Vec<&Word>>> = Vec::new();
+    if horizontal.len() >= 2 {
+        for word in words {
+            let row = index(&horizontal, word.top + word.height / 2);
+            if cells.len() <= row { cells.resize(row + 1, Vec::new()); }
+            place(&mut cells[row], &columns, word);
+        }
+        cells.retain(|row| !row.is_empty());
+    } else {
+        for row in layout::rows(words) {
+            let mut cell_row = Vec::new();
+            for word in row {
+                place(&mut cell_row, &columns, word);
+            }
+            cells.push(cell_row);
+        }
+    }
+
+    let width = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut table: Vec<Vec<String>> = cells
+        .into_iter()
+        .map(|row| {
+            let mut row: Vec<String> = row
+                .into_iter()
+                .map(|mut cell| {
+                    cell.sort_by_key(|w| (w.top / w.height.max(1), w.left));
+                    cell.iter().map(|w| &w.text[..]).collect::<Vec<&str>>().join(" ")
+                })
+                .collect();
+            row.resize(width, String::new());
+            row
+        })
+        .collect();
+
+    // Outer borders leave columns no word falls into
+    let used: Vec<bool> = (0..width).map(|c| table.iter().any(|row| !row[c].is_empty())).collect();
+    for row in table.iter_mut() {
+        let mut c = 0;
+        row.retain(|_| { c += 1; used[c - 1] });
+    }
+    table
+}
+
+pub fn format(table: &[Vec<String>], format: TableFormat) -> String {
+    match format {
+        TableFormat::Csv      => table
+            .iter()
+            .map(|row| row.iter().map(|cell| csv_field(cell)).collect::<Vec<String>>().join(","))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        TableFormat::Tsv      => table
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.replace(['\t', '\n'], " ")).collect::<Vec<String>>().join("\t"))
+            .collect::<Vec<String>>()
+            .join("\n"),
+        TableFormat::Markdown => {
+            let mut lines = Vec::new();
+            for (i, row) in table.iter().enumerate() {
+                let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|").replace('\n', " ")).collect();
+                lines.push(format!("| {} |", cells.join(" | ")));
+                // The first row doubles as the header
+                if i == 0 {
+                    lines.push(format!("|{}", " --- |".repeat(row.len())));
+                }
+            }
+            lines.join("\n")
+        }
+    }
+}
+
+fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+fn place<'a>(row: &mut Vec<Vec<&'a Word>>, columns: &[i32], word: &'a Word) {
+    let column = index(columns, word.left + word.width / 2);
+    if row.len() <= column { row.resize(column + 1, Vec::new()); }
+    row[column].push(word);
+}
+
+// Which of the spaces delimited by the sorted `boundaries` contains `position`.
+fn index(boundaries: &[i32], position: i32) -> usize {
+    boundaries.iter().filter(|&&b| b < position).count()
+}
+
+// Column boundaries in the middle of every vertical strip no word overlaps.
+fn column_gaps(words: &[Word]) -> Vec<i32> {
+    let mut spans: Vec<(i32, i32)> = words.iter().map(|w| (w.left, w.left + w.width)).collect();
+    spans.sort();
+    // Gaps narrower than a couple of characters are just spaces between words
+    let min_gap = (layout::cell_width(words) * 2.0).round() as i32;
+
+    let mut boundaries = Vec::new();
+    let mut right = spans[0].1;
+    for &(left, end) in &spans[1..] {
+        if left - right >= min_gap {
+            boundaries.push((right + left) / 2);
+        }
+        right = right.max(end);
+    }
+    boundaries
+}
+
+/* Long straight runs of pixels that stand out from the background, as
+ * (vertical x positions, horizontal y positions). Adjacent positions
+ * belonging to one thick line are merged.
+ */
+fn ruling_lines(image: &GrayImage) -> (Vec<i32>, Vec<i32>) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 { return (Vec::new(), Vec::new()); }
+    let background = background(image);
+    let is_ink = |x: u32, y: u32| (image.get_pixel(x, y)[0] as i16 - background).abs() > LINE_CONTRAST;
+
+    let longest_run = |length: u32, ink: &dyn Fn(u32) -> bool| {
+        let (mut longest, mut run) = (0, 0);
+        for i in 0..length {
+            run = if ink(i) { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+        longest
+    };
+
+    let vertical = (0..width)
+        .filter(|&x| longest_run(height, &|y| is_ink(x, y)) as f32 >= height as f32 * LINE_COVERAGE)
+        .map(|x| x as i32)
+        .collect();
+    let horizontal = (0..height)
+        .filter(|&y| longest_run(width, &|x| is_ink(x, y)) as f32 >= width as f32 * LINE_COVERAGE)
+        .map(|y| y as i32)
+        .collect();
+    (merge(vertical), merge(horizontal))
+}
+
+fn merge(positions: Vec<i32>) -> Vec<i32> {
+    let mut merged: Vec<Vec<i32>> = Vec::new();
+    for position in positions {
+        match merged.last_mut() {
+            Some(group) if position - group[group.len() - 1] <= 1 => group.push(position),
+            _ => merged.push(vec![position]),
+        }
+    }
+    merged.iter().map(|group| (group[0] + group[group.len() - 1]) / 2).collect()
+}
+
+// Most common luminance, which for a table is the cell background.
+fn background(image: &GrayImage) -> i16 {
+    let mut histogram = [0u32; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    (0..256).max_by_key(|&i| histogram[i]).unwrap_or(255) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::tests::word;
+    use image::Luma;
+
+    fn cells(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn columns_from_the_gaps_between_words() {
+        let blank = GrayImage::from_pixel(300, 60, Luma([255]));
+        let words = [
+            word(0, 0, "city"), word(120, 0, "country"),
+            word(0, 20, "New"), word(40, 20, "York"), word(120, 20, "USA"),
+            word(0, 40, "Lyon"), word(120, 40, "France"),
+        ];
+        assert_eq!(extract(&blank, &words), cells(&[&["city", "country"], &["New York", "USA"], &["Lyon", "France"]]));
+    }
+
+    #[test]
+    fn cells_from_ruling_lines() {
+        // A two by two grid, one cell with its text wrapped onto a second line
+        let mut image = GrayImage::from_pixel(200, 80, Luma([255]));
+        for y in 0..80 { for x in [0, 100, 199] { image.put_pixel(x, y, Luma([0])); } }
+        for x in 0..200 { for y in [0, 30, 79] { image.put_pixel(x, y, Luma([0])); } }
+        let words = [
+            word(10, 8, "name"), word(110, 8, "born"),
+            word(10, 36, "Ada"), word(10, 52, "Lovelace"), word(110, 36, "1815"),
+        ];
+        assert_eq!(extract(&image, &words), cells(&[&["name", "born"], &["Ada Lovelace", "1815"]]));
+    }
+
+    #[test]
+    fn an_empty_cell_keeps_its_place() {
+        let blank = GrayImage::from_pixel(300, 40, Luma([255]));
+        let words = [word(0, 0, "a"), word(100, 0, "b"), word(200, 0, "c"), word(0, 20, "d"), word(200, 20, "f")];
+        assert_eq!(extract(&blank, &words), cells(&[&["a", "b", "c"], &["d", "", "f"]]));
+    }
+
+    #[test]
+    fn nothing_recognized() {
+        assert!(extract(&GrayImage::new(10, 10), &[]).is_empty());
+    }
+
+    #[test]
+    fn csv_quotes_what_needs_it() {
+        let table = cells(&[&["name", "quote"], &["Smith, J.", "said \"hi\""]]);
+        assert_eq!(format(&table, TableFormat::Csv), "name,quote\n\"Smith, J.\",\"said \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn tsv_flattens_tabs() {
+        let table = cells(&[&["a\tb", "c"]]);
+        assert_eq!(format(&table, TableFormat::Tsv), "a b\tc");
+    }
+
+    #[test]
+    fn markdown_has_a_header_row() {
+        let table = cells(&[&["x", "y"], &["a|b", "c"]]);
+        assert_eq!(format(&table, TableFormat::Markdown), "| x | y |\n| --- | --- |\n| a\\|b | c |");
+    }
+}
@@ -0,0 +1,201 @@
+use std::io::Write;
+use flate2::{write::ZlibEncoder, Compression};
+use image::RgbImage;
+use crate::args::ocr::Word;
+
+// Screen pixels are laid out at 96 dpi, PDF user space is 72 units per inch.
+const SCALE: f32 = 72.0 / 96.0;
+
+// Objects shared by every page, pages start right after them.
+const CATALOG: usize = 1;
+const PAGES: usize = 2;
+const FONT: usize = 3;
+const CID_FONT: usize = 4;
+const FONT_DESCRIPTOR: usize = 5;
+const TO_UNICODE: usize = 6;
+const FIRST_PAGE: usize = 7;
+
+/* Build a searchable PDF, one page per capture:
+ *  - the capture is drawn as an image filling the page
+ *  - every OCR word is written on top of it with text render mode 3
+ *    (invisible), stretched to its box, so it can be searched and selected
+ * The text uses a glyphless Type0 font whose character codes are the UTF-16
+ * code units of the text, the ToUnicode map turns them back into text.
+ */
+pub fn document(pages: &[(RgbImage, Vec<Word>)]) -> Vec<u8> {
+    let mut objects: Vec<Vec<u8>> = vec![Vec::new(); FIRST_PAGE - 1 + pages.len() * 3];
+
+    let kids: Vec<String> = (0..pages.len()).map(|p| format!("{} 0 R", FIRST_PAGE + p * 3)).collect();
+    objects[CATALOG - 1] = format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES).into_bytes();
+    objects[PAGES - 1] = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes();
+    objects[FONT - 1] = format!(
+        "<< /Type /Font /Subtype /Type0 /BaseFont /GlyphLessFont /Encoding /Identity-H \
+         /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>", CID_FONT, TO_UNICODE).into_bytes();
+    objects[CID_FONT - 1] = format!(
+        "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /GlyphLessFont \
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+         /FontDescriptor {} 0 R /DW 500 /CIDToGIDMap /Identity >>", FONT_DESCRIPTOR).into_bytes();
+    objects[FONT_DESCRIPTOR - 1] = b"<< /Type /FontDescriptor /FontName /GlyphLessFont /Flags 5 \
+        /FontBBox [0 0 500 1000] /ItalicAngle 0 /Ascent 1000 /Descent 0 /CapHeight 1000 /StemV 80 >>".to_vec();
+    objects[TO_UNICODE - 1] = stream("", to_unicode().as_bytes());
+
+    for (p, (image, words)) in pages.iter().enumerate() {
+        let (page, contents, xobject) = (FIRST_PAGE + p * 3, FIRST_PAGE + p * 3 + 1, FIRST_PAGE + p * 3 + 2);
+        let width = image.width() as f32 * SCALE;
+        let height = image.height() as f32 * SCALE;
+
+        objects[page - 1] = format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {:.2} {:.2}] /Contents {} 0 R \
+             /Resources << /XObject << /Im0 {} 0 R >> /Font << /F1 {} 0 R >> >> >>",
+            PAGES, width, height, contents, xobject, FONT).into_bytes();
+        objects[contents - 1] = stream("", text_layer(width, height, words).as_bytes());
+        objects[xobject - 1] = stream(
+            &format!("/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+                      /BitsPerComponent 8 /Filter /FlateDecode", image.width(), image.height()),
+            &deflate(image.as_raw()));
+    }
+
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::new();
+    for (n, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", n + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(format!(
+        "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, CATALOG, xref).as_bytes());
+    pdf
+}
+
+// The page image followed by one invisible, horizontally scaled run of text per word.
+fn text_layer(width: f32, height: f32, words: &[Word]) -> String {
+    let mut content = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im0 Do Q\nBT 3 Tr\n", width, height);
+    for word in words {
+        let units: Vec<u16> = word.text.encode_utf16().collect();
+        if units.is_empty() || word.height <= 0 { continue; }
+        let size = word.height as f32 * SCALE;
+        // Every glyph of the font is half an em wide
+        let natural = units.len() as f32 * size * 0.5;
+        let stretch = 100.0 * word.width as f32 * SCALE / natural;
+        let x = word.left as f32 * SCALE;
+        let y = height - (word.top + word.height) as f32 * SCALE;
+        let hex: String = units.iter().map(|u| format!("{:04X}", u)).collect();
+        content.push_str(&format!("/F1 {:.2} Tf {:.2} Tz 1 0 0 1 {:.2} {:.2} Tm <{}> Tj\n", size, stretch, x, y, hex));
+    }
+    content.push_str("ET");
+    content
+}
+
+// Identity map from 2 byte codes to UTF-16, split into ranges that only vary in the last byte.
+fn to_unicode() -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n");
+    // At most 100 entries are allowed per block
+    for block in (0..256u32).collect::<Vec<u32>>().chunks(100) {
+        cmap.push_str(&format!("{} beginbfrange\n", block.len()));
+        for high in block {
+            cmap.push_str(&format!("<{:02X}00> <{:02X}FF> <{:02X}00>\n", high, high, high));
+        }
+        cmap.push_str("endbfrange\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend");
+    cmap
+}
+
+fn stream(dictionary: &str, data: &[u8]) -> Vec<u8> {
+    let length = format!("/Length {}", data.len());
+    let entries = if dictionary.is_empty() { length } else { format!("{} {}", dictionary, length) };
+    let mut object = format!("<< {} >>\nstream\n", entries).into_bytes();
+    object.extend_from_slice(data);
+    object.extend_from_slice(b"\nendstream");
+    object
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::ocr::tests::word;
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    fn count(haystack: &[u8], needle: &[u8]) -> usize {
+        haystack.windows(needle.len()).filter(|window| *window == needle).count()
+    }
+
+    fn pages(n: usize) -> Vec<(RgbImage, Vec<Word>)> {
+        (0..n).map(|p| (RgbImage::new(40 + p as u32, 30), vec![word(4, 4, "hi")])).collect()
+    }
+
+    #[test]
+    fn xref_offsets_point_at_their_objects() {
+        let pdf = document(&pages(2));
+        let tail = String::from_utf8_lossy(&pdf[find(&pdf, b"startxref\n").unwrap()..]).into_owned();
+        let xref: usize = tail.lines().nth(1).unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with(b"xref\n"));
+
+        let table = String::from_utf8_lossy(&pdf[xref..]).into_owned();
+        let mut lines = table.lines().skip(1);
+        let size: usize = lines.next().unwrap().split(' ').nth(1).unwrap().parse().unwrap();
+        assert_eq!(size, FIRST_PAGE + 2 * 3);
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+        for n in 1..size {
+            let entry = lines.next().unwrap();
+            assert!(entry.ends_with(" 00000 n "));
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", n).as_bytes()), "object {} is not at {}", n, offset);
+        }
+        assert!(table.contains(&format!("/Size {} ", size)));
+    }
+
+    #[test]
+    fn one_page_per_image() {
+        for n in [1, 3] {
+            let pdf = document(&pages(n));
+            assert_eq!(count(&pdf, b"/Type /Page /Parent"), n);
+            assert_eq!(count(&pdf, b"/Subtype /Image"), n);
+            assert!(find(&pdf, format!("/Count {} >>", n).as_bytes()).is_some());
+        }
+    }
+
+    #[test]
+    fn words_are_invisible_and_fill_their_box() {
+        // 96 pixels are 72 units, a 12 pixel tall word is set at 9 units, its two half em glyphs 9 units wide
+        let layer = text_layer(300.0, 225.0, &[word(96, 96, "ab")]);
+        assert!(layer.starts_with("q 300.00 0 0 225.00 0 0 cm /Im0 Do Q\nBT 3 Tr\n"));
+        assert!(layer.contains("/F1 9.00 Tf 166.67 Tz 1 0 0 1 72.00 144.00 Tm <00610062> Tj\n"));
+        assert!(layer.ends_with("ET"));
+    }
+
+    #[test]
+    fn words_are_utf16_and_empty_ones_skipped() {
+        let layer = text_layer(100.0, 100.0, &[word(0, 0, ""), word(0, 0, "é😀")]);
+        assert_eq!(layer.matches(" Tj").count(), 1);
+        assert!(layer.contains("<00E9D83DDE00> Tj"));
+    }
+
+    #[test]
+    fn to_unicode_covers_every_code() {
+        let cmap = to_unicode();
+        assert_eq!(cmap.matches("beginbfrange").count(), 3);
+        assert!(cmap.contains("<0000> <00FF> <0000>\n"));
+        assert!(cmap.contains("<FF00> <FFFF> <FF00>\n"));
+        assert_eq!(cmap.lines().filter(|line| line.ends_with("00>") && line.starts_with('<')).count(), 256);
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use log::info;
+
+use super::annotate::Annotation;
+use super::image_proc;
+use super::redact;
+
+// Bumped whenever a field changes meaning, older projects are refused rather than misread.
+pub const VERSION: u32 = 1;
+pub const EXTENSION: &str = "pictura";
+
+/* A capture kept editable: the pixels before anything was drawn on them,
+ * the regions cut out of them and the annotations as shapes, redactions
+ * included. The redactions are burnt into the pixels as well, what they
+ * hide never reaches the disk. Everything is in global physical pixels so
+ * reopening it puts the overlay back where the capture was taken.
+ */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    pub image: PathBuf,       // the original pixels, relative to the project file
+    pub origin: (i32, i32),   // global position of the image's top left corner
+    pub regions: Vec<Region>, // the crop, the last one is the selection
+    pub composite: bool,
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/* Write `name.pictura` and the original pixels as `name.orig.png` next to
+ * `output`, the exported capture. Only the part of `frame`, with its top
+ * left corner at `origin`, that the regions span is kept, redacted, and
+ * the regions only as far as they are on it.
+ */
+pub fn save(output: &Path,
+            frame: &RgbaImage,
+            origin: &image_proc::Point,
+            regions: &[image_proc::Rectangle],
+            annotations: &[Annotation],
+            composite: bool)
+    -> Result<PathBuf, String> {
+
+    // Only what of the regions is on the frame, so the shapes line up with the pixels when reopened
+    let regions: Vec<image_proc::Rectangle> = regions.iter().filter_map(|r| r.within(frame, origin)).collect();
+    if regions.is_empty() { return Err("nothing selected".to_string()); }
+    let left = regions.iter().map(|r| r.tl.x).min().unwrap();
+    let top = regions.iter().map(|r| r.tl.y).min().unwrap();
+    let right = regions.iter().map(|r| r.br.x).max().unwrap();
+    let bottom = regions.iter().map(|r| r.br.y).max().unwrap();
+    let mut original = image::imageops::crop_imm(frame,
+                                                 (left - origin.x) as u32,
+                                                 (top - origin.y) as u32,
+                                                 (right - left) as u32,
+                                                 (bottom - top) as u32).to_image();
+    let redactions: Vec<redact::Redaction> = annotations.iter().filter_map(|a| a.redaction()).collect();
+    redact::apply(&mut original, &redactions, (left, top));
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let image = PathBuf::from(format!("{}.orig.png", stem));
+    let path = output.with_file_name(format!("{}.{}", stem, EXTENSION));
+    original.save(output.with_file_name(&image)).map_err(|e| e.to_string())?;
+
+    let project = Project {
+        version: VERSION,
+        image,
+        origin: (left, top),
+        regions: regions.iter().map(|r| Region {
+            x: r.tl.x,
+            y: r.tl.y,
+            width: (r.br.x - r.tl.x) as u32,
+            height: (r.br.y - r.tl.y) as u32,
+        }).collect(),
+        composite,
+        annotations: annotations.to_vec(),
+    };
+    let json = serde_json::to_string_pretty(&project).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| e.to_string())?;
+    info!("Project: {:?}", path);
+    Ok(path)
+}
+
+// A project and its original pixels.
+pub fn load(path: &Path) -> Result<(Project, RgbaImage), String> {
+    let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let project: Project = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if project.version != VERSION {
+        return Err(format!("unsupported project version {}", project.version));
+    }
+    let image = path.parent().unwrap_or(Path::new("")).join(&project.image);
+    let image = image::open(&image).map_err(|e| format!("{}: {}", image.display(), e))?.to_rgba8();
+    Ok((project, image))
+}
@@ -0,0 +1,214 @@
+use std::io::Cursor;
+use image::{ImageOutputFormat, RgbaImage};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::ocr;
+mod detect;
+pub use detect::Detector;
+
+// Side of the blocks a pixelated region is averaged over, in pixels.
+const BLOCK: u32 = 16;
+// Box blur passes, three come close to a gaussian.
+const PASSES: usize = 3;
+
+/* How a region is made unreadable. Pixelate and fill throw the pixels
+ * away, a blur keeps some of them around and can be partly undone.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    Pixelate,
+    Fill,
+    Blur,
+}
+
+impl Method {
+    pub fn parse(name: &str) -> Option<Method> {
+        match &*name.to_lowercase() {
+            "pixelate" => Some(Method::Pixelate),
+            "fill"     => Some(Method::Fill),
+            "blur"     => Some(Method::Blur),
+            _          => None,
+        }
+    }
+}
+
+// A region to redact in global physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Redaction {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub method: Method,
+}
+
+impl Redaction {
+    // `x,y,w,h` as given to --redact.
+    pub fn parse(spec: &str, method: Method) -> Option<Redaction> {
+        let values: Vec<&str> = spec.split(',').map(str::trim).collect();
+        match values[..] {
+            [x, y, width, height] => Some(Redaction {
+                x: x.parse().ok()?,
+                y: y.parse().ok()?,
+                width: width.parse().ok()?,
+                height: height.parse().ok()?,
+                method,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width as i32 && y < self.y + self.height as i32
+    }
+}
+
+/* What --auto-redact looks for, how found words are hidden and how the
+ * screen is read to find them.
+ */
+pub struct Auto<'a> {
+    pub detectors: Vec<Detector>,
+    pub method: Method,
+    pub options: &'a ocr::Options,
+}
+
+impl<'a> Auto<'a> {
+    // The built-in detectors followed by the user's `(name, regex)` patterns.
+    pub fn new(patterns: &[(String, String)], method: Method, options: &'a ocr::Options) -> Auto<'a> {
+        let mut detectors = detect::builtin();
+        detectors.extend(patterns.iter().filter_map(|(name, pattern)| Detector::custom(name, pattern)));
+        Auto { detectors, method, options }
+    }
+}
+
+/* OCR `image`, whose top left corner sits at `origin` in global physical
+ * pixels, and return a redaction over every word a detector matched.
+ * Each one is reported on stdout, masked so the report doesn't leak what
+ * the image no longer shows.
+ */
+pub fn detect(image: &RgbaImage, origin: (i32, i32), auto: &Auto) -> Vec<Redaction> {
+    let mut png = Cursor::new(Vec::new());
+    if let Err(e) = image.write_to(&mut png, ImageOutputFormat::Png) {
+        warn!("Could not prepare the image for auto redaction: {}", e);
+        return Vec::new();
+    }
+    let words = ocr::words(&[png.into_inner()], auto.options).pop().unwrap_or_default();
+
+    let mut redactions = Vec::new();
+    for finding in detect::scan(&words, &auto.detectors) {
+        let left = finding.words.iter().map(|w| w.left).min().unwrap_or(0);
+        let top = finding.words.iter().map(|w| w.top).min().unwrap_or(0);
+        println!("Redacted {} {} at {},{}", finding.name, mask(&finding.text), origin.0 + left, origin.1 + top);
+        // A little margin so antialiased edges of the glyphs go too
+        redactions.extend(finding.words.iter().map(|word| Redaction {
+            x: origin.0 + word.left - 2,
+            y: origin.1 + word.top - 2,
+            width: (word.width + 4).max(0) as u32,
+            height: (word.height + 4).max(0) as u32,
+            method: auto.method,
+        }));
+    }
+    redactions
+}
+
+// Keep the first and last two characters of anything long enough to recognize it by.
+fn mask(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= 8 { return "*".repeat(chars.len()); }
+    let (head, tail): (String, String) = (chars[..2].iter().collect(), chars[chars.len() - 2..].iter().collect());
+    format!("{}{}{}", head, "*".repeat(chars.len() - 4), tail)
+}
+
+/* Redact `image`, whose top left corner sits at `origin` in global
+ * physical pixels. Only pixels inside a region are read, so nothing
+ * from around it bleeds in or out.
+ */
+pub fn apply(image: &mut RgbaImage, redactions: &[Redaction], origin: (i32, i32)) {
+    for redaction in redactions {
+        let left = (redaction.x - origin.0).max(0);
+        let top = (redaction.y - origin.1).max(0);
+        let right = (redaction.x + redaction.width as i32 - origin.0).min(image.width() as i32);
+        let bottom = (redaction.y + redaction.height as i32 - origin.1).min(image.height() as i32);
+        if right <= left || bottom <= top { continue; }
+        let (left, top, right, bottom) = (left as u32, top as u32, right as u32, bottom as u32);
+        match redaction.method {
+            Method::Fill     => fill(image, (left, top, right, bottom), [0, 0, 0, 255]),
+            Method::Pixelate => {
+                for y in (top..bottom).step_by(BLOCK as usize) {
+                    for x in (left..right).step_by(BLOCK as usize) {
+                        let block = (x, y, (x + BLOCK).min(right), (y + BLOCK).min(bottom));
+                        let color = average(image, block);
+                        fill(image, block, color);
+                    }
+                }
+            }
+            Method::Blur     => blur(image, (left, top, right, bottom)),
+        }
+    }
+}
+
+fn fill(image: &mut RgbaImage, (left, top, right, bottom): (u32, u32, u32, u32), color: [u8; 4]) {
+    for y in top..bottom {
+        for x in left..right {
+            image.get_pixel_mut(x, y).0 = color;
+        }
+    }
+}
+
+fn average(image: &RgbaImage, (left, top, right, bottom): (u32, u32, u32, u32)) -> [u8; 4] {
+    let mut sum = [0u64; 4];
+    for y in top..bottom {
+        for x in left..right {
+            for (total, channel) in sum.iter_mut().zip(image.get_pixel(x, y).0) {
+                *total += channel as u64;
+            }
+        }
+    }
+    let count = ((right - left) * (bottom - top)) as u64;
+    sum.map(|total| (total / count) as u8)
+}
+
+// Repeated box blurs with a radius of a quarter of the shorter side.
+fn blur(image: &mut RgbaImage, (left, top, right, bottom): (u32, u32, u32, u32)) {
+    let (width, height) = ((right - left) as usize, (bottom - top) as usize);
+    let radius = (width.min(height) / 4).max(2);
+    let mut pixels: Vec<[f32; 4]> = Vec::with_capacity(width * height);
+    for y in top..bottom {
+        for x in left..right {
+            pixels.push(image.get_pixel(x, y).0.map(|channel| channel as f32));
+        }
+    }
+    blur_pixels(&mut pixels, width, height, radius);
+    for (i, pixel) in pixels.iter().enumerate() {
+        let (x, y) = (left + (i % width) as u32, top + (i / width) as u32);
+        image.get_pixel_mut(x, y).0 = pixel.map(|channel| channel.round() as u8);
+    }
+}
+
+// Blur `width` by `height` pixels, row by row, in place.
+pub(super) fn blur_pixels(pixels: &mut [[f32; 4]], width: usize, height: usize, radius: usize) {
+    for _ in 0..PASSES {
+        for row in 0..height {
+            box_blur(pixels, row * width, 1, width, radius);
+        }
+        for column in 0..width {
+            box_blur(pixels, column, width, height, radius);
+        }
+    }
+}
+
+// Average every one of `count` pixels from `start` apart by `stride` with its neighbors within `radius`.
+fn box_blur(pixels: &mut [[f32; 4]], start: usize, stride: usize, count: usize, radius: usize) {
+    // Running totals make every average two lookups, whatever the radius
+    let mut totals = vec![[0.0f64; 4]; count + 1];
+    for i in 0..count {
+        let pixel = pixels[start + i * stride];
+        totals[i + 1] = [0, 1, 2, 3].map(|c| totals[i][c] + pixel[c] as f64);
+    }
+    for i in 0..count {
+        let (from, to) = (i.saturating_sub(radius), (i + radius + 1).min(count));
+        pixels[start + i * stride] = [0, 1, 2, 3].map(|c| ((totals[to][c] - totals[from][c]) / (to - from) as f64) as f32);
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::thread;
+use std::time::Duration;
+use image::RgbaImage;
+use log::{info, warn};
+
+use super::config::Config;
+use super::image_proc::{self, Point};
+use super::window;
+
+// Unchanged frames in a row that mean the wheel hit the bottom of the page.
+const BOTTOM: u32 = 2;
+// How long the region has to stay still before scrolling by hand is over.
+const HOLD: Duration = Duration::from_secs(4);
+// Rows still match with one in this many pixels different, for a scrollbar, a caret or anything animated.
+const TOLERANCE: usize = 20;
+
+pub struct Options {
+    pub wheel: bool,       // scroll with fake mouse wheel events instead of waiting on the user
+    pub clicks: u32,       // wheel notches between frames
+    pub delay: Duration,   // for the page to settle before the next frame
+    pub frames: usize,     // at most
+}
+
+impl Options {
+    // `scroll_clicks`, `scroll_delay` in milliseconds and `scroll_frames` in the config.
+    pub fn load(config: &Config) -> Options {
+        let number = |key: &str, default: u64| match config.get(key) {
+            Some(value) => value.parse::<u64>().unwrap_or_else(|_| {
+                warn!("Invalid {} {}, expected a number", key, value);
+                default
+            }),
+            None        => default,
+        };
+        Options {
+            wheel: true,
+            clicks: number("scroll_clicks", 3) as u32,
+            delay: Duration::from_millis(number("scroll_delay", 400)),
+            frames: number("scroll_frames", 50) as usize,
+        }
+    }
+}
+
+/* Frames of the region from `tl` to `br` while it scrolls down, until it
+ * stops moving. The wheel is turned over its center where X11 allows it,
+ * otherwise the user scrolls and stopping for a few seconds ends it.
+ */
+pub fn capture(tl: &Point, br: &Point, options: &Options) -> Vec<RgbaImage> {
+    let (x, y) = ((tl.x + br.x) / 2, (tl.y + br.y) / 2);
+    let mut wheel = options.wheel;
+    let hold = || (HOLD.as_millis() / options.delay.as_millis().max(1)).max(1) as u32;
+    if !wheel { println!("Scroll down, stop for {} seconds when done", HOLD.as_secs()); }
+
+    let mut frames = vec![image_proc::frame(tl, br)];
+    let mut still = 0;
+    while frames.len() < options.frames {
+        if wheel && !window::scroll(x, y, options.clicks) {
+            println!("Could not scroll, scroll down by hand and stop for {} seconds when done", HOLD.as_secs());
+            wheel = false;
+        }
+        thread::sleep(options.delay);
+        let frame = image_proc::frame(tl, br);
+        if frames.last().is_some_and(|last| Rows::new(last).same(&Rows::new(&frame))) {
+            still += 1;
+            let enough = if wheel { BOTTOM } else { hold() };
+            if still >= enough { break; }
+            continue;
+        }
+        still = 0;
+        frames.push(frame);
+    }
+    info!("Captured {} frames", frames.len());
+    frames
+}
+
+/* One tall image out of frames of the same region taken while it scrolled
+ * down. Rows that stay put across every frame, like a page header or a
+ * chat's input box, are kept once at the top and bottom. Between them
+ * each frame is matched against the one before: the smallest scroll for
+ * which all rows both show match wins and only the rows it brought in are
+ * added. Rows match with a few pixels off, see `TOLERANCE`, so a moving
+ * scrollbar thumb or a blinking caret doesn't throw it off. A frame that
+ * didn't scroll adds nothing, one that shares no rows with the one before
+ * is added whole. None without frames.
+ */
+pub fn stitch(frames: &[RgbaImage]) -> Option<RgbaImage> {
+    let first = frames.first()?;
+    let (width, height) = first.dimensions();
+    if width == 0 || height == 0 { return Some(first.clone()); }
+    let mut kept: Vec<Rows> = Vec::new();
+    for frame in frames {
+        if frame.dimensions() != (width, height) {
+            warn!("Skipping a {}x{} frame, the first is {}x{}", frame.width(), frame.height(), width, height);
+            continue;
+        }
+        let rows = Rows::new(frame);
+        if !kept.last().is_some_and(|last| last.same(&rows)) { kept.push(rows); }
+    }
+
+    let height = height as usize;
+    let pairs: Vec<(&Rows, &Rows)> = kept.iter().zip(kept.iter().skip(1)).collect();
+    let header = pairs.iter().map(|(a, b)| (0..height).take_while(|&row| a.alike(row, b, row)).count()).min().unwrap_or(0);
+    let footer = pairs.iter().map(|(a, b)| (0..height).rev().take_while(|&row| a.alike(row, b, row)).count()).min().unwrap_or(0);
+    let body = header..height - footer;
+    let length = body.len();
+
+    // Which row of which frame goes where, top to bottom
+    let mut layout: Vec<(usize, usize)> = (0..body.end).map(|row| (0, row)).collect();
+    for (k, (above, below)) in pairs.iter().enumerate() {
+        let scrolled = (1..length).find(|&s| {
+            (body.start..body.end - s).all(|row| above.alike(row + s, below, row))
+        }).unwrap_or_else(|| {
+            warn!("Frame {} shares nothing with the one before, scrolled too far?", k + 1);
+            length
+        });
+        info!("Frame {} scrolled by {} rows", k + 1, scrolled);
+        layout.extend((body.end - scrolled..body.end).map(|row| (k + 1, row)));
+    }
+    layout.extend((body.end..height).map(|row| (kept.len() - 1, row)));
+
+    let mut pixels = Vec::with_capacity(layout.len() * width as usize * 4);
+    for (k, row) in layout.iter() {
+        pixels.extend_from_slice(kept[*k].row(*row));
+    }
+    RgbaImage::from_raw(width, layout.len() as u32, pixels)
+}
+
+// The rows of a frame with their hashes, so rows that are the same compare in one step.
+struct Rows<'a> {
+    image: &'a RgbaImage,
+    hashes: Vec<u64>,
+}
+
+impl<'a> Rows<'a> {
+    fn new(image: &'a RgbaImage) -> Rows<'a> {
+        let hashes = image.as_raw().chunks(image.width() as usize * 4).map(|row| {
+            let mut hasher = DefaultHasher::new();
+            row.hash(&mut hasher);
+            hasher.finish()
+        }).collect();
+        Rows { image, hashes }
+    }
+
+    fn row(&self, row: usize) -> &'a [u8] {
+        let stride = self.image.width() as usize * 4;
+        &self.image.as_raw()[row * stride..(row + 1) * stride]
+    }
+
+    // Whether `row` matches row `other_row` of `other`, but for a few pixels.
+    fn alike(&self, row: usize, other: &Rows, other_row: usize) -> bool {
+        if self.hashes[row] == other.hashes[other_row] { return true; }
+        let allowed = self.image.width() as usize / TOLERANCE;
+        self.row(row).chunks_exact(4)
+            .zip(other.row(other_row).chunks_exact(4))
+            .filter(|(a, b)| a != b)
+            .nth(allowed)
+            .is_none()
+    }
+
+    // Whether every row matches, the frame didn't scroll.
+    fn same(&self, other: &Rows) -> bool {
+        self.hashes.len() == other.hashes.len() && (0..self.hashes.len()).all(|row| self.alike(row, other, row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const WIDTH: u32 = 40;
+
+    // A page whose rows all differ, with blank stretches between them like lines of text.
+    fn page(height: u32) -> RgbaImage {
+        RgbaImage::from_fn(WIDTH, height, |x, y| match (y / 5) % 3 {
+            0 => Rgba([255, 255, 255, 255]),
+            _ => Rgba([(y * 37 % 251) as u8, (x * 13 + y) as u8, (y / 3) as u8, 255]),
+        })
+    }
+
+    // `height` rows of `page` from `top`, below a `header` and above a `footer` that never move.
+    fn view(page: &RgbaImage, top: u32, height: u32, header: u32, footer: u32) -> RgbaImage {
+        RgbaImage::from_fn(WIDTH, height, |x, y| {
+            if y < header { Rgba([40, 40, 40, 255]) }
+            else if y >= height - footer { Rgba([200, 0, 0, 255]) }
+            else { *page.get_pixel(x, top + y - header) }
+        })
+    }
+
+    fn frames(page: &RgbaImage, tops: &[u32], height: u32) -> Vec<RgbaImage> {
+        tops.iter().map(|&top| view(page, top, height, 0, 0)).collect()
+    }
+
+    #[test]
+    fn no_scroll_is_one_frame() {
+        let page = page(50);
+        let frames = frames(&page, &[0, 0, 0], 50);
+        assert_eq!(stitch(&frames), Some(page));
+    }
+
+    #[test]
+    fn partial_overlaps_add_the_new_rows() {
+        let page = page(200);
+        let frames = frames(&page, &[0, 30, 30, 75, 110, 150], 50);
+        assert_eq!(stitch(&frames), Some(page));
+    }
+
+    #[test]
+    fn sticky_header_and_footer_are_kept_once() {
+        let page = page(200);
+        let frames: Vec<RgbaImage> = [0, 40, 95, 130].iter().map(|&top| view(&page, top, 90, 12, 8)).collect();
+        assert_eq!(stitch(&frames), Some(view(&page, 0, 220, 12, 8)));
+    }
+
+    #[test]
+    fn no_overlap_adds_the_frame_whole() {
+        let page = page(200);
+        let frames = frames(&page, &[0, 110], 50);
+        let expected = RgbaImage::from_fn(WIDTH, 100, |x, y| *page.get_pixel(x, if y < 50 { y } else { y + 60 }));
+        assert_eq!(stitch(&frames), Some(expected));
+    }
+
+    #[test]
+    fn a_moving_scrollbar_is_tolerated() {
+        let page = page(200);
+        let tops = [0, 35, 80, 120, 150];
+        // The rightmost column is a scrollbar, its thumb moving down as the page does
+        let frames: Vec<RgbaImage> = tops.iter().map(|&top| {
+            let mut frame = view(&page, top, 50, 0, 0);
+            for y in 0..50 {
+                let thumb = y >= top / 4 && y < top / 4 + 12;
+                frame.put_pixel(WIDTH - 1, y, if thumb { Rgba([90, 90, 90, 255]) } else { Rgba([230, 230, 230, 255]) });
+            }
+            frame
+        }).collect();
+        let stitched = stitch(&frames).unwrap();
+        assert_eq!(stitched.dimensions(), (WIDTH, 200));
+        let content = |image: &RgbaImage| image::imageops::crop_imm(image, 0, 0, WIDTH - 1, 200).to_image();
+        assert_eq!(content(&stitched), content(&page));
+    }
+}
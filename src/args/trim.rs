@@ -0,0 +1,91 @@
+use image::{Rgba, RgbaImage};
+
+// How far any channel may stray from a margin's color, when `--trim` isn't given one.
+pub const TOLERANCE: u8 = 8;
+
+/* The part of `image` left once its uniformly colored margins are peeled
+ * off, as x, y, width and height. Rows go from the top and bottom for as
+ * long as each is one color across, so a panel above the desktop or a
+ * gradient down the screen goes too. The columns left and right are only
+ * looked at between those rows and must also keep the color of the
+ * outermost one, or the border of the window inside would go with them.
+ * None when nothing would be left.
+ */
+pub fn bounds(image: &RgbaImage, tolerance: u8) -> Option<(u32, u32, u32, u32)> {
+    let (width, height) = image.dimensions();
+    let near = |a: &Rgba<u8>, b: &Rgba<u8>| a.0.iter().zip(b.0.iter()).all(|(a, b)| a.abs_diff(*b) <= tolerance);
+    let row = |y: u32| (0..width).all(|x| near(image.get_pixel(x, y), image.get_pixel(0, y)));
+
+    let (mut top, mut bottom) = (0, height);
+    while top < bottom && row(top) { top += 1; }
+    while bottom > top && row(bottom - 1) { bottom -= 1; }
+    if top == bottom { return None; }
+
+    let column = |x: u32, reference: &Rgba<u8>| (top..bottom).all(|y| near(image.get_pixel(x, y), reference));
+    let (mut left, mut right) = (0, width);
+    let reference = *image.get_pixel(0, top);
+    while left < right && column(left, &reference) { left += 1; }
+    let reference = *image.get_pixel(width - 1, top);
+    while right > left && column(right - 1, &reference) { right -= 1; }
+
+    if left == right { None } else { Some((left, top, right - left, bottom - top)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAY: Rgba<u8> = Rgba([128, 128, 128, 255]);
+    const RED: Rgba<u8> = Rgba([200, 30, 30, 255]);
+
+    // A `width` by `height` image of `background` with `fill` from `x`, `y` on.
+    fn boxed(width: u32, height: u32, background: Rgba<u8>, (x, y, w, h): (u32, u32, u32, u32), fill: Rgba<u8>) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |px, py| {
+            if px >= x && px < x + w && py >= y && py < y + h { fill } else { background }
+        })
+    }
+
+    #[test]
+    fn cuts_uniform_margins() {
+        let image = boxed(50, 40, GRAY, (10, 5, 20, 12), RED);
+        assert_eq!(bounds(&image, 0), Some((10, 5, 20, 12)));
+    }
+
+    #[test]
+    fn nothing_to_cut() {
+        let mut image = RgbaImage::from_pixel(20, 10, RED);
+        image.put_pixel(0, 0, GRAY);
+        image.put_pixel(19, 9, GRAY);
+        assert_eq!(bounds(&image, 0), Some((0, 0, 20, 10)));
+    }
+
+    #[test]
+    fn a_single_color_leaves_nothing() {
+        assert_eq!(bounds(&RgbaImage::from_pixel(20, 10, GRAY), 0), None);
+    }
+
+    #[test]
+    fn noise_within_the_tolerance_goes_too() {
+        let mut image = boxed(30, 30, GRAY, (10, 10, 10, 10), RED);
+        image.put_pixel(2, 3, Rgba([133, 125, 128, 255]));
+        assert_eq!(bounds(&image, 0), Some((2, 3, 18, 17)));
+        assert_eq!(bounds(&image, TOLERANCE), Some((10, 10, 10, 10)));
+    }
+
+    #[test]
+    fn rows_of_a_gradient_go() {
+        let mut image = RgbaImage::from_fn(30, 30, |_, y| Rgba([y as u8 * 8, 0, 0, 255]));
+        for (x, y) in [(12, 10), (17, 19)] { image.put_pixel(x, y, RED); }
+        assert_eq!(bounds(&image, 0), Some((0, 10, 30, 10)));
+    }
+
+    #[test]
+    fn a_window_border_stays() {
+        // A gray window with a red one pixel border on a blue desktop, plus a panel on top
+        let blue = Rgba([20, 40, 160, 255]);
+        let mut image = boxed(60, 50, blue, (10, 10, 40, 30), RED);
+        for y in 11..39 { for x in 11..49 { image.put_pixel(x, y, GRAY); } }
+        for x in 0..60 { image.put_pixel(x, 0, Rgba([0, 0, 0, 255])); }
+        assert_eq!(bounds(&image, 0), Some((10, 10, 40, 30)));
+    }
+}
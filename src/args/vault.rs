@@ -0,0 +1,305 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::{Datelike, Local, NaiveDateTime};
+use serde_json::Value;
+use log::{info, warn, error};
+
+use super::annotate::Annotation;
+use super::{excalidraw, ocr, Format};
+
+// Daily note names when the vault doesn't set a format, as Obsidian does.
+const DAILY_FORMAT: &str = "YYYY-MM-DD";
+// Moment.js tokens Obsidian date formats are written in, longest first, and what they become.
+const TOKENS: [(&str, Token); 35] = [
+    ("YYYY", Token::Chrono("%Y")), ("GGGG", Token::Chrono("%G")), ("MMMM", Token::Chrono("%B")),
+    ("DDDD", Token::Chrono("%j")), ("dddd", Token::Chrono("%A")),
+    ("MMM", Token::Chrono("%b")), ("DDD", Token::Chrono("%-j")), ("ddd", Token::Chrono("%a")),
+    ("YY", Token::Chrono("%y")), ("GG", Token::Chrono("%g")), ("MM", Token::Chrono("%m")),
+    ("DD", Token::Chrono("%d")), ("Do", Token::Ordinal), ("dd", Token::Weekday),
+    ("WW", Token::Chrono("%V")), ("ww", Token::Chrono("%V")), ("HH", Token::Chrono("%H")),
+    ("hh", Token::Chrono("%I")), ("mm", Token::Chrono("%M")), ("ss", Token::Chrono("%S")),
+    ("M", Token::Chrono("%-m")), ("D", Token::Chrono("%-d")), ("d", Token::Chrono("%w")),
+    ("E", Token::Chrono("%u")), ("e", Token::Chrono("%w")), ("Q", Token::Quarter),
+    ("W", Token::Chrono("%-V")), ("w", Token::Chrono("%-V")), ("H", Token::Chrono("%-H")),
+    ("h", Token::Chrono("%-I")), ("m", Token::Chrono("%-M")), ("s", Token::Chrono("%-S")),
+    ("A", Token::Chrono("%p")), ("a", Token::Chrono("%P")), ("X", Token::Chrono("%s")),
+];
+
+// What a moment.js token stands for. Locale weeks (`w`) are taken as ISO weeks (`W`).
+enum Token {
+    Chrono(&'static str), // the same thing in chrono
+    Ordinal,              // day of the month as 1st, 2nd, ...
+    Weekday,              // the two letter day name, Mo, Tu, ...
+    Quarter,              // 1 to 4
+}
+
+// An Obsidian vault to save into, and the note to embed the capture in if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub vault: PathBuf,
+    pub note: Option<Note>,
+    pub ocr: bool, // put the recognized text under the embed
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Note {
+    Daily,         // today's, per the daily notes settings
+    Named(String), // relative to the vault, `.md` may be left out
+}
+
+// `~/notes` as given on the command line or in the config.
+pub fn expand(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _                      => PathBuf::from(path),
+    }
+}
+
+/* Save the png encoded images into the vault's attachment folder, named
+ * after the time like Obsidian's pasted images, and append an embed of
+ * each to the note. Returns the first file.
+ */
+pub fn save(target: &Target,
+            images: &[Vec<u8>],
+            format: Format,
+            options: &ocr::Options,
+            shapes: &[Vec<Annotation>])
+    -> Option<PathBuf> {
+
+    let vault = &target.vault;
+    if !vault.join(".obsidian").is_dir() {
+        warn!("{} has no .obsidian folder, saving into it anyway", vault.display());
+    }
+    let note = target.note.as_ref().map(|note| match note {
+        Note::Daily       => daily(vault),
+        Note::Named(name) => {
+            let path = vault.join(name);
+            if path.extension().is_some() { path } else { path.with_extension("md") }
+        }
+    });
+    let folder = attachments(vault, note.as_deref());
+    if let Err(e) = fs::create_dir_all(&folder) {
+        error!("Could not create {}: {}", folder.display(), e);
+        return None;
+    }
+
+    let stamp = Local::now().format("%Y%m%d%H%M%S");
+    let name = |suffix: &str| folder.join(format!("Capture {}{}", stamp, suffix));
+    let files: Vec<(PathBuf, Vec<u8>)> = match format {
+        Format::Png if images.len() == 1 => vec![(name(".png"), images[0].clone())],
+        Format::Png        => images.iter().enumerate().map(|(k, image)| (name(&format!(" {}.png", k)), image.clone())).collect(),
+        Format::Webp if images.len() == 1 => vec![(name(".webp"), super::webp(&images[0]))],
+        Format::Webp       => images.iter().enumerate().map(|(k, image)| (name(&format!(" {}.webp", k)), super::webp(image))).collect(),
+        Format::Pdf        => vec![(name(".pdf"), super::document(images, options))],
+        Format::Excalidraw => vec![(name(".excalidraw"), excalidraw::scene(images, shapes).into_bytes())],
+    };
+    for (path, contents) in &files {
+        if let Err(e) = fs::write(path, contents) {
+            error!("Could not save {}: {}", path.display(), e);
+            return None;
+        }
+        println!("Saved to {}", path.display());
+    }
+
+    if let Some(note) = note {
+        let mut block: String = files.iter()
+            .map(|(path, _)| format!("![[{}]]\n", path.file_name().unwrap().to_string_lossy()))
+            .collect();
+        if target.ocr {
+            let text = ocr::run(images, options).join("\n");
+            if !text.trim().is_empty() { block.push_str(&format!("\n{}\n", text.trim_end())); }
+        }
+        match append(&note, &block) {
+            Ok(()) => println!("Embedded in {}", note.display()),
+            Err(e) => error!("Could not write to {}: {}", note.display(), e),
+        }
+    }
+    files.into_iter().next().map(|(path, _)| path)
+}
+
+/* Where new attachments go per `attachmentFolderPath` in `.obsidian/app.json`:
+ *  - unset or `/`      the vault's root
+ *  - `./` or `./name`  next to the note, or in `name` next to it
+ *  - anything else     that folder, relative to the vault
+ */
+fn attachments(vault: &Path, note: Option<&Path>) -> PathBuf {
+    let setting = settings(vault, "app.json")
+        .and_then(|app| app["attachmentFolderPath"].as_str().map(String::from))
+        .unwrap_or_default();
+    info!("Attachment folder setting: {:?}", setting);
+    match setting.strip_prefix("./") {
+        Some(folder) => note.and_then(Path::parent).unwrap_or(vault).join(folder),
+        None         => vault.join(setting.trim_start_matches('/')),
+    }
+}
+
+// Today's note per `.obsidian/daily-notes.json`, its `folder` and date `format`.
+fn daily(vault: &Path) -> PathBuf {
+    let settings = settings(vault, "daily-notes.json").unwrap_or(Value::Null);
+    let folder = settings["folder"].as_str().unwrap_or("").trim_start_matches('/');
+    let format = settings["format"].as_str().filter(|f| !f.is_empty()).unwrap_or(DAILY_FORMAT);
+    let name = moment(format, &Local::now().naive_local());
+    vault.join(folder).join(format!("{}.md", name))
+}
+
+fn settings(vault: &Path, file: &str) -> Option<Value> {
+    let json = fs::read_to_string(vault.join(".obsidian").join(file)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+// `date` in a moment.js format, `[...]` is literal text.
+fn moment(format: &str, date: &NaiveDateTime) -> String {
+    let mut text = String::new();
+    let mut rest = format;
+    while let Some(c) = rest.chars().next() {
+        if c == '[' {
+            let end = rest.find(']').unwrap_or(rest.len());
+            text.push_str(&rest[1..end]);
+            rest = &rest[(end + 1).min(rest.len())..];
+        } else if let Some((token, kind)) = TOKENS.iter().find(|(token, _)| rest.starts_with(token)) {
+            match kind {
+                Token::Chrono(spec) => text.push_str(&date.format(spec).to_string()),
+                Token::Ordinal      => {
+                    let day = date.day();
+                    let suffix = match (day % 10, day % 100) {
+                        (_, 11..=13) => "th",
+                        (1, _)       => "st",
+                        (2, _)       => "nd",
+                        (3, _)       => "rd",
+                        _            => "th",
+                    };
+                    text.push_str(&format!("{}{}", day, suffix));
+                }
+                Token::Weekday      => text.push_str(&date.format("%a").to_string()[..2]),
+                Token::Quarter      => text.push_str(&((date.month() - 1) / 3 + 1).to_string()),
+            }
+            rest = &rest[token.len()..];
+        } else {
+            // Moment keeps these as they are too, but a name made of them is likely not what was meant
+            if c.is_ascii_alphabetic() { warn!("Unknown token {:?} in the date format {:?}, kept as text", c, format); }
+            text.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    text
+}
+
+// At the end of the note after a blank line, creating it and its folder when needed.
+fn append(note: &Path, block: &str) -> std::io::Result<()> {
+    if let Some(folder) = note.parent() { fs::create_dir_all(folder)?; }
+    let existing = fs::read_to_string(note).unwrap_or_default();
+    let separator = match existing.as_str() {
+        ""                             => "",
+        text if text.ends_with("\n\n") => "",
+        text if text.ends_with('\n')   => "\n",
+        _                              => "\n\n",
+    };
+    let mut file = OpenOptions::new().create(true).append(true).open(note)?;
+    write!(file, "{}{}", separator, block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(14, 5, 9).unwrap()
+    }
+
+    // An empty vault under the temp folder, with these `.obsidian` settings files.
+    fn vault(name: &str, settings: &[(&str, &str)]) -> PathBuf {
+        let vault = env::temp_dir().join(format!("vault-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&vault);
+        fs::create_dir_all(vault.join(".obsidian")).unwrap();
+        for (file, json) in settings { fs::write(vault.join(".obsidian").join(file), json).unwrap(); }
+        vault
+    }
+
+    #[test]
+    fn moment_tokens() {
+        let date = at(2024, 3, 7);
+        assert_eq!(moment(DAILY_FORMAT, &date), "2024-03-07");
+        assert_eq!(moment("dddd, MMMM Do YYYY", &date), "Thursday, March 7th 2024");
+        assert_eq!(moment("ddd dd d E [Q]Q [W]WW", &date), "Thu Th 4 4 Q1 W10");
+        assert_eq!(moment("YY/M/D DDDD HH:mm:ss h A", &date), "24/3/7 067 14:05:09 2 PM");
+        assert_eq!(moment("[Today is] YYYY 100%", &date), "Today is 2024 100%");
+    }
+
+    #[test]
+    fn moment_ordinals() {
+        let days: Vec<String> = [1, 2, 3, 4, 11, 12, 13, 21, 22, 23, 31].iter()
+            .map(|&day| moment("Do", &at(2024, 1, day)))
+            .collect();
+        assert_eq!(days, ["1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd", "23rd", "31st"]);
+    }
+
+    #[test]
+    fn moment_keeps_unknown_letters() {
+        assert_eq!(moment("YYYY-MM-DD [at] x", &at(2024, 11, 30)), "2024-11-30 at x");
+    }
+
+    #[test]
+    fn attachments_per_setting() {
+        let note = Path::new("/vault/Journal/today.md");
+        let root = vault("root", &[]);
+        assert_eq!(attachments(&root, Some(note)), root);
+        let slash = vault("slash", &[("app.json", r#"{"attachmentFolderPath": "/"}"#)]);
+        assert_eq!(attachments(&slash, Some(note)), slash);
+        let next = vault("next", &[("app.json", r#"{"attachmentFolderPath": "./"}"#)]);
+        assert_eq!(attachments(&next, Some(note)), Path::new("/vault/Journal"));
+        assert_eq!(attachments(&next, None), next);
+        let under = vault("under", &[("app.json", r#"{"attachmentFolderPath": "./images"}"#)]);
+        assert_eq!(attachments(&under, Some(note)), Path::new("/vault/Journal/images"));
+        let folder = vault("folder", &[("app.json", r#"{"attachmentFolderPath": "Assets/Captures"}"#)]);
+        assert_eq!(attachments(&folder, Some(note)), folder.join("Assets/Captures"));
+        for vault in [root, slash, next, under, folder] { fs::remove_dir_all(vault).unwrap(); }
+    }
+
+    #[test]
+    fn append_separates_with_a_blank_line() {
+        let vault = vault("append", &[]);
+        let note = vault.join("Notes/new.md");
+        append(&note, "![[a.png]]\n").unwrap();
+        assert_eq!(fs::read_to_string(&note).unwrap(), "![[a.png]]\n");
+        append(&note, "![[b.png]]\n").unwrap();
+        assert_eq!(fs::read_to_string(&note).unwrap(), "![[a.png]]\n\n![[b.png]]\n");
+        for (existing, expected) in [("text", "text\n\nblock\n"), ("text\n", "text\n\nblock\n"), ("text\n\n", "text\n\nblock\n")] {
+            fs::write(&note, existing).unwrap();
+            append(&note, "block\n").unwrap();
+            assert_eq!(fs::read_to_string(&note).unwrap(), expected);
+        }
+        fs::remove_dir_all(vault).unwrap();
+    }
+
+    #[test]
+    fn saves_into_the_daily_note() {
+        let vault = vault("daily", &[
+            ("daily-notes.json", r#"{"folder": "/Journal", "format": "YYYY/MMMM Do"}"#),
+            ("app.json", r#"{"attachmentFolderPath": "./attachments"}"#),
+        ]);
+        let note = vault.join("Journal").join(format!("{}.md", moment("YYYY/MMMM Do", &Local::now().naive_local())));
+        assert_eq!(daily(&vault), note);
+        fs::create_dir_all(note.parent().unwrap()).unwrap();
+        fs::write(&note, "# Today").unwrap();
+
+        let target = Target { vault: vault.clone(), note: Some(Note::Daily), ocr: false };
+        let options = ocr::Options::default();
+        let first = save(&target, &[vec![1, 2, 3]], Format::Png, &options, &[]).unwrap();
+        let second = save(&target, &[vec![4], vec![5]], Format::Png, &options, &[]).unwrap();
+        assert_eq!(first.parent(), Some(note.parent().unwrap().join("attachments").as_path()));
+        assert_eq!(fs::read(&second).unwrap(), vec![4]);
+
+        let embed = |path: &Path| format!("![[{}]]", path.file_name().unwrap().to_string_lossy());
+        let text = fs::read_to_string(&note).unwrap();
+        let blocks: Vec<&str> = text.split("\n\n").collect();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0], "# Today");
+        assert_eq!(blocks[1], embed(&first));
+        assert!(blocks[2].starts_with(&embed(&second)));
+        assert_eq!(blocks[2].lines().count(), 2);
+        fs::remove_dir_all(vault).unwrap();
+    }
+}
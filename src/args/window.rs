@@ -0,0 +1,184 @@
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{self, AtomEnum, ConnectionExt, MapState};
+use x11rb::protocol::xtest::ConnectionExt as _;
+use x11rb::rust_connection::RustConnection;
+use regex::RegexBuilder;
+use log::{info, warn};
+
+// The mouse wheel turning down is button 5 to X11.
+const WHEEL_DOWN: u8 = 5;
+
+// A top-level window on the X11 screen, in global physical pixels.
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub id: u32,
+    pub title: String,
+    pub class: Vec<String>,          // WM_CLASS instance and class name
+    pub x: i32,                      // client area, without decorations
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub frame: (u32, u32, u32, u32), // decorations as left, right, top, bottom
+}
+
+impl Window {
+    // Bounds as (x, y, width, height), optionally grown by the decorations.
+    pub fn bounds(&self, decorations: bool) -> (i32, i32, u32, u32) {
+        if !decorations {
+            return (self.x, self.y, self.width, self.height);
+        }
+        let (left, right, top, bottom) = self.frame;
+        (self.x - left as i32, self.y - top as i32, self.width + left + right, self.height + top + bottom)
+    }
+}
+
+/* Visible top-level windows, topmost first. Uses the window manager's
+ * _NET_CLIENT_LIST_STACKING and falls back to the root window's children
+ * without one. Empty when there is no X11 display.
+ */
+pub fn list() -> Vec<Window> {
+    let (conn, root) = match connect() {
+        Some(connection) => connection,
+        None             => return Vec::new(),
+    };
+
+    let mut stacking = property32(&conn, root, "_NET_CLIENT_LIST_STACKING");
+    if stacking.is_empty() {
+        stacking = conn.query_tree(root)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|tree| tree.children)
+            .unwrap_or_default();
+    }
+
+    // Both lists are ordered bottom to top
+    let windows: Vec<Window> = stacking
+        .into_iter()
+        .rev()
+        .filter_map(|id| window(&conn, root, id))
+        .collect();
+    info!("Found {} windows", windows.len());
+    windows
+}
+
+// The window with keyboard focus according to the window manager.
+pub fn active() -> Option<Window> {
+    let (conn, root) = connect()?;
+    let id = *property32(&conn, root, "_NET_ACTIVE_WINDOW").first()?;
+    window(&conn, root, id)
+}
+
+/* The topmost window matching `query`, tried in order as
+ *  - a window id, decimal or 0x hex
+ *  - a WM_CLASS instance or class name, ignoring case
+ *  - a regex on the title, ignoring case
+ */
+pub fn find(query: &str) -> Option<Window> {
+    let windows = list();
+    let id = match query.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None      => query.parse::<u32>().ok(),
+    };
+    if let Some(window) = id.and_then(|id| windows.iter().find(|w| w.id == id)) {
+        return Some(window.clone());
+    }
+    if let Some(window) = windows.iter().find(|w| w.class.iter().any(|c| c.eq_ignore_ascii_case(query))) {
+        return Some(window.clone());
+    }
+    let title = match RegexBuilder::new(query).case_insensitive(true).build() {
+        Ok(title) => title,
+        Err(e)    => {
+            warn!("Invalid title regex {}: {}", query, e);
+            return None;
+        }
+    };
+    windows.into_iter().find(|w| title.is_match(&w.title))
+}
+
+/* Turn the mouse wheel `clicks` notches down with the pointer moved to
+ * global `x`, `y`, so whatever is under it scrolls. Goes through the XTEST
+ * extension and is false without it or without an X11 display.
+ */
+pub fn scroll(x: i32, y: i32, clicks: u32) -> bool {
+    let (conn, root) = match connect() {
+        Some(connection) => connection,
+        None             => return false,
+    };
+    if conn.xtest_get_version(2, 2).ok().and_then(|cookie| cookie.reply().ok()).is_none() {
+        warn!("No XTEST extension, can't scroll");
+        return false;
+    }
+    let fake = |kind: u8, detail: u8| {
+        conn.xtest_fake_input(kind, detail, x11rb::CURRENT_TIME, root, x as i16, y as i16, 0).is_ok()
+    };
+    fake(xproto::MOTION_NOTIFY_EVENT, 0)
+        && (0..clicks).all(|_| fake(xproto::BUTTON_PRESS_EVENT, WHEEL_DOWN) && fake(xproto::BUTTON_RELEASE_EVENT, WHEEL_DOWN))
+        && conn.flush().is_ok()
+}
+
+fn connect() -> Option<(RustConnection, u32)> {
+    match x11rb::connect(None) {
+        Ok((conn, screen)) => {
+            let root = conn.setup().roots[screen].root;
+            Some((conn, root))
+        }
+        Err(e)             => {
+            warn!("No X11 display, window detection disabled: {}", e);
+            None
+        }
+    }
+}
+
+fn window(conn: &RustConnection, root: u32, id: u32) -> Option<Window> {
+    let attributes = conn.get_window_attributes(id).ok()?.reply().ok()?;
+    if attributes.map_state != MapState::VIEWABLE { return None; }
+    let geometry = conn.get_geometry(id).ok()?.reply().ok()?;
+    let position = conn.translate_coordinates(id, root, 0, 0).ok()?.reply().ok()?;
+    let frame = match property32(conn, id, "_NET_FRAME_EXTENTS")[..] {
+        [left, right, top, bottom] => (left, right, top, bottom),
+        _                          => (0, 0, 0, 0),
+    };
+    // Prefer the UTF-8 _NET_WM_NAME, WM_NAME is latin-1 at best
+    let mut title = property8(conn, id, "_NET_WM_NAME");
+    if title.is_empty() { title = property8(conn, id, "WM_NAME"); }
+    let class = property8(conn, id, "WM_CLASS")
+        .split('\0')
+        .filter(|c| !c.is_empty())
+        .map(String::from)
+        .collect();
+    Some(Window {
+        id,
+        title,
+        class,
+        x: position.dst_x as i32,
+        y: position.dst_y as i32,
+        width: geometry.width as u32,
+        height: geometry.height as u32,
+        frame,
+    })
+}
+
+fn atom(conn: &RustConnection, name: &str) -> Option<u32> {
+    Some(conn.intern_atom(false, name.as_bytes()).ok()?.reply().ok()?.atom)
+}
+
+fn property32(conn: &RustConnection, window: u32, name: &str) -> Vec<u32> {
+    let reply = atom(conn, name).and_then(|atom| {
+        conn.get_property(false, window, atom, AtomEnum::ANY, 0, u32::MAX).ok()?.reply().ok()
+    });
+    let values = match reply.as_ref().and_then(|reply| reply.value32()) {
+        Some(values) => values.collect(),
+        None         => Vec::new(),
+    };
+    values
+}
+
+fn property8(conn: &RustConnection, window: u32, name: &str) -> String {
+    let reply = atom(conn, name).and_then(|atom| {
+        conn.get_property(false, window, atom, AtomEnum::ANY, 0, u32::MAX).ok()?.reply().ok()
+    });
+    match reply {
+        Some(reply) if reply.format == 8 => String::from_utf8_lossy(&reply.value).into_owned(),
+        _                                => String::new(),
+    }
+}
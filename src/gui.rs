@@ -1,18 +1,21 @@
 use iced::window::Level;
-use iced::widget::{ column, container };
+use iced::widget::{ button, column, container, image::{ Handle, Image }, text, Row, Space };
 use iced::mouse;
-use iced::{ Alignment, Length, window, Point };
+use iced::{ ContentFit, Length, Padding, window, Point, Size, Vector };
 use iced_winit::conversion;
 use iced_winit::runtime::Debug;
 use winit::dpi::{ LogicalPosition, PhysicalPosition };
 use log::info;
+use std::time::{ Duration, Instant };
 
 use iced_wgpu::{wgpu, Backend, Renderer};
 use iced_winit::{futures, winit, Clipboard};
 use winit::event::{MouseButton, ElementState, KeyboardInput, VirtualKeyCode};
 
 use crate::args;
-use crate::gui::theme::{ Theme, widget::Element };
+use crate::args::action::{self, Action, Capture, Selection};
+use crate::args::annotate::{ Annotation, Shape, Tool };
+use crate::gui::theme::{ self as style, Theme, widget::Element };
 
 use winit::{
     event::{Event as winEvent, ModifiersState, WindowEvent},
@@ -22,9 +25,63 @@ use iced_winit::runtime::{Command, Program};
 
 mod theme;
 pub mod rectangle;
+pub mod selection;
+pub mod stack;
+pub mod loupe;
+pub mod pin;
+pub mod annotate;
+pub mod editor;
 pub use rectangle as rect;
+use selection::Drag;
 
-pub fn run(tl: PhysicalPosition<f64>, br: PhysicalPosition<f64>) {
+// Two clicks within this interval confirm the selection.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+// Pixels across the loupe and the size each one is magnified to.
+const LOUPE_SIZE: usize = 15;
+const LOUPE_ZOOM: f32 = 8.0;
+// Toolbar buttons and what they do, in order.
+const TOOLS: [(&str, Action); 7] = [
+    ("Save", Action::Save),
+    ("Save as", Action::SaveAs(None)),
+    ("Copy", Action::Copy),
+    ("OCR", Action::CopyText),
+    ("Annotate", Action::Annotate),
+    ("Pin", Action::Pin),
+    ("Cancel", Action::Cancel),
+];
+const TOOL_SIZE: Size = Size::new(72.0, 28.0);
+// Stroke colors and widths in physical pixels offered while annotating.
+const COLORS: [[u8; 4]; 5] = [
+    [0xfb, 0x49, 0x34, 0xff],
+    [0xfa, 0xbd, 0x2f, 0xff],
+    [0xb8, 0xbb, 0x26, 0xff],
+    [0x83, 0xa5, 0x98, 0xff],
+    [0xeb, 0xdb, 0xb2, 0xff],
+];
+const STROKES: [f32; 3] = [2.0, 4.0, 8.0];
+const STROKE_BUTTON: f32 = 40.0;
+// How far off a stroke a click still picks the shape, in logical pixels.
+const HIT_SLOP: f32 = 4.0;
+
+// Options of the overlay, from the command line and config.
+#[derive(Default)]
+pub struct Settings {
+    pub frame: Option<image::RgbaImage>, // the screens as they were before the overlay opened
+    pub border_width: Option<f32>,
+    pub border_color: Option<iced::Color>,
+    pub loupe_size: Option<usize>, // 0 hides the loupe
+    pub loupe_zoom: Option<f32>,
+    pub windows: Vec<iced::Rectangle>, // snap targets in global physical pixels, topmost first
+    pub ratio: Option<f32>,            // width / height held by the ratio lock (R)
+    pub presets: Vec<(String, u32, u32)>, // name and physical size, spawned with 1-9
+    pub composite: bool,               // paste all regions into one image instead of one each
+    pub json: bool,                    // print the regions as JSON on capture
+    pub project: bool,                 // keep annotated captures editable as a .pictura project
+    pub regions: Vec<iced::Rectangle>, // reopened from a project in global physical pixels, the last is the selection
+    pub annotations: Vec<Annotation>,  // reopened from a project, annotating starts right away
+}
+
+pub fn run(tl: PhysicalPosition<f64>, br: PhysicalPosition<f64>, settings: Settings) {
     let event_loop = EventLoop::new();
     let win_window = iced_winit::settings::Window {
         resizable: false,
@@ -60,7 +117,7 @@ pub fn run(tl: PhysicalPosition<f64>, br: PhysicalPosition<f64>) {
     let mut cursor_position = None;
     let mut clipboard = Clipboard::connect(&window);
 
-    let modifiers = ModifiersState::default();
+    let mut modifiers = ModifiersState::default();
 
     let default_backend = wgpu::Backends::PRIMARY;
 
@@ -106,7 +163,24 @@ pub fn run(tl: PhysicalPosition<f64>, br: PhysicalPosition<f64>) {
         },
         );
 
-    let app = App::new(());
+    // Monitors in logical window coordinates, for cycling through them with Tab
+    let scale = window.scale_factor();
+    let monitors = window.available_monitors().map(|monitor| {
+        let position = monitor.position();
+        let size = monitor.size();
+        iced::Rectangle::new(
+            Point::new(((position.x as f64 - tl.x) / scale) as f32, ((position.y as f64 - tl.y) / scale) as f32),
+            Size::new((size.width as f64 / scale) as f32, (size.height as f64 / scale) as f32),
+        )
+    }).collect();
+
+    let app = App::new(Flags {
+        origin: Point::new(tl.x as f32, tl.y as f32),
+        scale: scale as f32,
+        size: Size::new(physical_size.width as f32 / scale as f32, physical_size.height as f32 / scale as f32),
+        monitors,
+        settings,
+    });
     let mut debug = Debug::new();
     let mut renderer = Renderer::new(Backend::new(
             &device,
@@ -121,10 +195,8 @@ pub fn run(tl: PhysicalPosition<f64>, br: PhysicalPosition<f64>) {
         &mut renderer,
         &mut debug,
         );
-    let mut pressed = false;
-    let mut released = false;
-    let mut pressed_pos = None;
-    let mut released_pos = None;
+    let mut captured = false;
+    let mut pressed = false; // the left mouse button
     event_loop.run(move |event, _, control_flow| {
         // You should change this if you want to render continuosly
         *control_flow = ControlFlow::Wait;
@@ -135,40 +207,92 @@ pub fn run(tl: PhysicalPosition<f64>, br: PhysicalPosition<f64>) {
                     WindowEvent::CursorMoved { position, .. } => {
                         let pos: LogicalPosition<f64> = position.to_logical(window.current_monitor().unwrap().scale_factor()); 
                         _state.queue_message(Message::OnMouseMoved(Point { x: pos.x as f32, y: pos.y as f32 }));
-                        if pressed { pressed_pos = Some(position); }
-                        else { released_pos= Some(position); }
                         cursor_position = Some(position)
                     }
-                    WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(VirtualKeyCode::Q),
-                                    ..
-                                },
+                    WindowEvent::CloseRequested => {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    // While a text label is typed every key belongs to it
+                    WindowEvent::ReceivedCharacter(c) if _state.program().typing() => {
+                        _state.queue_message(Message::Char(c));
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Q | VirtualKeyCode::Escape),
                                 ..
-                        } => { 
-                            *control_flow = ControlFlow::Exit; 
+                            },
+                            ..
+                    } if !_state.program().typing() => { 
+                        *control_flow = ControlFlow::Exit; 
+                    }
+                    // Shift and Ctrl only shape the selection with the mouse down, not a nudge or an undo
+                    WindowEvent::ModifiersChanged(new_modifiers) => {
+                        if pressed && modifiers.shift() != new_modifiers.shift() {
+                            _state.queue_message(Message::Square(new_modifiers.shift()));
                         }
+                        if pressed && modifiers.ctrl() != new_modifiers.ctrl() {
+                            _state.queue_message(Message::Add(new_modifiers.ctrl()));
+                        }
+                        modifiers = new_modifiers;
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                            ..
+                    } if !_state.program().typing() => {
+                        // One physical pixel, or ten with Shift
+                        let step = if modifiers.shift() { 10.0 } else { 1.0 } / window.scale_factor() as f32;
+                        let direction = match key {
+                            VirtualKeyCode::Left  => Some(Vector::new(-step, 0.0)),
+                            VirtualKeyCode::Right => Some(Vector::new(step, 0.0)),
+                            VirtualKeyCode::Up    => Some(Vector::new(0.0, -step)),
+                            VirtualKeyCode::Down  => Some(Vector::new(0.0, step)),
+                            _                     => None,
+                        };
+                        match (key, direction) {
+                            (_, Some(direction)) if modifiers.alt() => _state.queue_message(Message::Resize(direction)),
+                            (_, Some(direction))          => _state.queue_message(Message::Nudge(direction)),
+                            (VirtualKeyCode::Return, _)   => _state.queue_message(Message::Confirm),
+                            (VirtualKeyCode::Tab, _)      => _state.queue_message(Message::NextMonitor(modifiers.shift())),
+                            (VirtualKeyCode::R, _)        => _state.queue_message(Message::ToggleRatio),
+                            (VirtualKeyCode::C, _) if modifiers.ctrl() => _state.queue_message(Message::Act(Action::Copy)),
+                            (VirtualKeyCode::Z, _) if modifiers.ctrl() && modifiers.shift() => _state.queue_message(Message::Redo),
+                            (VirtualKeyCode::Z, _) if modifiers.ctrl() => _state.queue_message(Message::Undo),
+                            (VirtualKeyCode::Y, _) if modifiers.ctrl() => _state.queue_message(Message::Redo),
+                            (VirtualKeyCode::Delete | VirtualKeyCode::Back, _) => _state.queue_message(Message::Delete),
+                            (key, _)                      => {
+                                let presets = [
+                                    VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3,
+                                    VirtualKeyCode::Key4, VirtualKeyCode::Key5, VirtualKeyCode::Key6,
+                                    VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+                                ];
+                                if let Some(n) = presets.iter().position(|&k| k == key) {
+                                    _state.queue_message(Message::Preset(n));
+                                }
+                            }
+                        }
+                    }
                     WindowEvent::MouseInput { state, button, .. } => {
                         match button { 
                             MouseButton::Left => {
                                 match state {
                                     ElementState::Pressed => {
                                         pressed = true;
+                                        _state.queue_message(Message::Square(modifiers.shift()));
+                                        _state.queue_message(Message::Add(modifiers.ctrl()));
                                         _state.queue_message(Message::OnMousePressed);
                                     }
                                     ElementState::Released => {
-                                        if pressed { released = true; }
+                                        pressed = false;
                                         _state.queue_message(Message::OnMouseReleased);
-                                        *control_flow = ControlFlow::Exit; 
-                                        if released {
-                                            args::capture(( PhysicalPosition::new(pressed_pos.unwrap().x + tl.x,
-                                                                                  pressed_pos.unwrap().y + tl.y), 
-                                                            PhysicalPosition::new(released_pos.unwrap().x + tl.x,
-                                                                                  released_pos.unwrap().y + tl.y)));
-                                        }                                   
+                                        _state.queue_message(Message::Square(false));
+                                        _state.queue_message(Message::Add(false));
                                     }
                                 }
                             }
@@ -210,6 +334,18 @@ pub fn run(tl: PhysicalPosition<f64>, br: PhysicalPosition<f64>) {
                         &mut debug,
                         );
 
+                    // An action was picked, hand it the regions and leave
+                    if let Some((action, selection)) = _state.program().done() {
+                        if !captured {
+                            captured = true;
+                            *control_flow = ControlFlow::Exit;
+                            // Out of the way of save dialogs and while waiting on the clipboard
+                            window.set_visible(false);
+                            action::dispatch(&action, Capture::Overlay(selection));
+                        }
+                        return;
+                    }
+
                     // and request a redraw
                     window.request_redraw();
                 }
@@ -275,27 +411,250 @@ pub enum Message {
     OnMousePressed,
     OnMouseMoved(Point),
     OnMouseReleased,
+    Confirm,
+    Nudge(Vector),       // move the selection by this offset
+    Resize(Vector),      // grow or shrink the selection's bottom right corner
+    NextMonitor(bool),   // select the next monitor, the previous one if true
+    Square(bool),        // Shift held while dragging, lock the selection to a square
+    ToggleRatio,         // lock the selection to the configured aspect ratio
+    Preset(usize),       // spawn a frame of a fixed size preset at the cursor
+    Add(bool),           // Ctrl held on a press, the new drag keeps the selection as another region
+    Act(Action),         // finish with this action on the regions
+    Tool(Option<Tool>),  // annotate with this tool, none picks existing shapes
+    Color([u8; 4]),      // annotation stroke color
+    Stroke(f32),         // annotation stroke width in physical pixels
+    Char(char),          // typed into the text label
+    Undo,
+    Redo,
+    Delete,              // remove the picked shape
+}
+
+pub struct Flags {
+    pub origin: Point,                  // global physical position of the window's top left corner
+    pub scale: f32,                     // physical pixels per logical pixel
+    pub size: Size,                     // logical size of the window
+    pub monitors: Vec<iced::Rectangle>, // in logical window coordinates
+    pub settings: Settings,
 }
 
 pub struct App {
-    width: f32,
-    height: f32,
-    pressed: bool,
-    released: bool,
-    cursor_pressed_position: Point,
-    cursor_released_position: Point,
+    origin: Point,
+    scale: f32,
+    size: Size,
+    cursor: Point,
+    selection: Option<iced::Rectangle>,
+    regions: Vec<iced::Rectangle>, // earlier selections kept with Ctrl, captured along with it
+    drag: Option<Drag>,
+    last_release: Option<Instant>,
+    confirmed: bool, // the toolbar is up
+    action: Option<Action>,
+    monitors: Vec<iced::Rectangle>,
+    monitor: Option<usize>,
+    frame: Option<Handle>,
+    windows: Vec<iced::Rectangle>,
+    square: bool,
+    ratio_lock: bool,
+    preset: Option<usize>,
+    add: bool,
+    annotating: bool,
+    editor: editor::Editor,
+    tool: Option<Tool>,    // none picks existing shapes
+    color: [u8; 4],
+    stroke: f32,
+    drawing: bool,         // the new annotation follows the cursor
+    typing: bool,          // the new annotation is a text label taking the keys
+    moving: Option<(f32, f32)>, // the picked shape follows the cursor, last seen here in global physical pixels
+    layer: Option<Handle>, // the annotations rasterized over the selection
+    settings: Settings,
 }
 
 impl App {
-    fn new(_flags: ()) -> App {
-            App {
-                width: 0f32,
-                height: 0f32,
-                pressed: false,
-                released: false,
-                cursor_pressed_position: Point {x:0.0, y:0.0},
-                cursor_released_position: Point {x:0.0, y:0.0},
+    fn new(flags: Flags) -> App {
+            let logical = |r: &iced::Rectangle| iced::Rectangle {
+                x: (r.x - flags.origin.x) / flags.scale,
+                y: (r.y - flags.origin.y) / flags.scale,
+                width: r.width / flags.scale,
+                height: r.height / flags.scale,
+            };
+            let mut regions: Vec<iced::Rectangle> = flags.settings.regions.iter().map(logical).collect();
+            let selection = regions.pop();
+            let mut app = App {
+                origin: flags.origin,
+                scale: flags.scale,
+                size: flags.size,
+                cursor: Point {x:0.0, y:0.0},
+                selection,
+                regions,
+                drag: None,
+                last_release: None,
+                confirmed: selection.is_some(),
+                action: None,
+                monitors: flags.monitors,
+                monitor: None,
+                // Uploaded once, the handle is cheap to clone into every view
+                frame: flags.settings.frame.as_ref().map(|frame| {
+                    Handle::from_pixels(frame.width(), frame.height(), frame.as_raw().clone())
+                }),
+                windows: flags.settings.windows.iter().map(logical).collect(),
+                square: false,
+                ratio_lock: false,
+                preset: None,
+                add: false,
+                annotating: selection.is_some(),
+                editor: editor::Editor::with(flags.settings.annotations.clone()),
+                tool: Some(Tool::Arrow),
+                color: COLORS[0],
+                stroke: STROKES[0],
+                drawing: false,
+                typing: false,
+                moving: None,
+                layer: None,
+                settings: flags.settings,
+            };
+            app.redraw();
+            app
+    }
+
+    // The window under the cursor while nothing is selected, what a click would capture.
+    fn hovered(&self) -> Option<iced::Rectangle> {
+        let selected = self.selection.is_some_and(|s| s.width >= 1.0 || s.height >= 1.0);
+        if selected || matches!(self.drag, Some(Drag::Move(_) | Drag::Resize(_))) { return None; }
+        self.windows.iter().find(|w| w.contains(self.cursor)).copied()
+    }
+
+    // The aspect ratio the selection is held to right now, if any.
+    fn ratio(&self) -> Option<f32> {
+        if self.square { return Some(1.0); }
+        if self.ratio_lock { self.settings.ratio } else { None }
+    }
+
+    // The picked action and what it acts on, once there is one.
+    pub fn done(&self) -> Option<(Action, Selection<'_>)> {
+        let action = self.action.clone()?;
+        let regions = self.regions.iter().copied().chain(self.selection).map(|r| {
+            let (x, y) = self.global(Point::new(r.x, r.y));
+            iced::Rectangle { x, y, width: r.width * self.scale, height: r.height * self.scale }
+        }).collect();
+        Some((action, Selection {
+            frame: self.settings.frame.as_ref().map(|frame| (frame, (self.origin.x as i32, self.origin.y as i32))),
+            regions,
+            annotations: self.editor.annotations().to_vec(),
+            composite: self.settings.composite,
+            json: self.settings.json,
+            project: self.settings.project,
+        }))
+    }
+
+    // Whether keys go to a text label instead of the overlay.
+    pub fn typing(&self) -> bool {
+        self.typing
+    }
+
+    // A logical window position in global physical pixels.
+    fn global(&self, point: Point) -> (f32, f32) {
+        (self.origin.x + point.x * self.scale, self.origin.y + point.y * self.scale)
+    }
+
+    /* Rasterize the annotations over the selection, exactly as they will be
+     * captured. Redacted areas show the frozen frame with the redaction
+     * applied, everything else is left transparent.
+     */
+    fn redraw(&mut self) {
+        let s = match self.selection {
+            Some(s) => s,
+            None    => return,
+        };
+        let (x, y) = self.global(Point::new(s.x, s.y));
+        let (width, height) = ((s.width * self.scale).round() as u32, (s.height * self.scale).round() as u32);
+        if width == 0 || height == 0 { return; }
+        let (x, y) = (x.round() as i32, y.round() as i32);
+        let mut layer = image::RgbaImage::new(width, height);
+
+        let annotations = self.editor.annotations();
+        let redactions: Vec<_> = annotations.iter().filter_map(Annotation::redaction).collect();
+        if let (false, Some(frame)) = (redactions.is_empty(), &self.settings.frame) {
+            let (left, top) = (x - self.origin.x as i32, y - self.origin.y as i32);
+            let mut below = image::imageops::crop_imm(frame, left.max(0) as u32, top.max(0) as u32, width, height).to_image();
+            args::redact::apply(&mut below, &redactions, (x, y));
+            for (px, py, pixel) in below.enumerate_pixels() {
+                if redactions.iter().any(|r| r.contains(x + px as i32, y + py as i32)) {
+                    layer.put_pixel(px, py, *pixel);
+                }
             }
+        }
+        args::annotate::rasterize(&mut layer, annotations, (x as f32, y as f32));
+        self.layer = Some(Handle::from_pixels(width, height, layer.into_raw()));
+    }
+
+    // Stop typing into the text label, dropping it when it stayed empty.
+    fn finish_text(&mut self) {
+        if !self.typing { return; }
+        self.typing = false;
+        if matches!(self.editor.current(), Some(Annotation { shape: Shape::Text(_, text), .. }) if text.is_empty()) {
+            self.editor.discard();
+        } else {
+            self.editor.commit();
+        }
+    }
+
+    // After the text label being typed, in logical window coordinates.
+    fn caret(&self) -> Option<iced::Rectangle> {
+        if !self.typing { return None; }
+        let annotation = self.editor.current()?;
+        let (at, text) = match &annotation.shape {
+            Shape::Text(at, text) => (at, text),
+            _                     => return None,
+        };
+        let scale = annotation.text_scale();
+        let x = at.0 + (text.chars().count() * 8) as f32 * scale;
+        Some(iced::Rectangle {
+            x: (x - self.origin.x) / self.scale,
+            y: (at.1 - self.origin.y) / self.scale,
+            width: 2.0,
+            height: 8.0 * scale / self.scale,
+        })
+    }
+
+    // Around the picked shape, in logical window coordinates.
+    fn picked(&self) -> Option<iced::Rectangle> {
+        let annotation = &self.editor.annotations()[self.editor.selected()?];
+        let (left, top, right, bottom) = annotation.bounds();
+        Some(iced::Rectangle {
+            x: (left - self.origin.x) / self.scale,
+            y: (top - self.origin.y) / self.scale,
+            width: (right - left) / self.scale,
+            height: (bottom - top) / self.scale,
+        })
+    }
+
+    // The toolbar's actions, less Annotate once annotating.
+    fn actions(&self) -> Vec<(&'static str, Action)> {
+        TOOLS.into_iter().filter(|(_, action)| !(self.annotating && *action == Action::Annotate)).collect()
+    }
+
+    /* Where the toolbar goes while it is up: under the selection's right
+     * edge, above it when there is no room below, inside it when neither fits.
+     */
+    fn toolbar_bounds(&self) -> Option<iced::Rectangle> {
+        let s = self.selection?;
+        if !self.confirmed || self.drag.is_some() { return None; }
+        let spacing = 2.0;
+        let actions = self.actions().len() as f32 * (TOOL_SIZE.width + spacing) - spacing;
+        let size = if self.annotating {
+            // The annotation tools, colors and widths go in a row above the actions
+            let tools = (Tool::ALL.len() + 1) as f32 * (TOOL_SIZE.width + spacing)
+                + COLORS.len() as f32 * (TOOL_SIZE.height + spacing)
+                + STROKES.len() as f32 * (STROKE_BUTTON + spacing) - spacing;
+            Size::new(actions.max(tools) + 2.0 * spacing, 2.0 * TOOL_SIZE.height + 3.0 * spacing)
+        } else {
+            Size::new(actions + 2.0 * spacing, TOOL_SIZE.height + 2.0 * spacing)
+        };
+        let (gap, bottom) = (8.0, s.y + s.height);
+        let y = if bottom + gap + size.height <= self.size.height { bottom + gap }
+                else if s.y - gap - size.height >= 0.0 { s.y - gap - size.height }
+                else { bottom - gap - size.height };
+        let x = (s.x + s.width - size.width).min(self.size.width - size.width).max(0.0);
+        Some(iced::Rectangle::new(Point::new(x, y), size))
     }
 }
 
@@ -304,51 +663,452 @@ impl Program for App {
     type Renderer = Renderer<Theme>;
 
     fn update(&mut self, _message: Message) -> Command<Message> {
+        // The selection stays put while it is annotated
+        if self.annotating && matches!(_message, Message::Nudge(_) | Message::Resize(_) | Message::NextMonitor(_) | Message::Preset(_)) {
+            return Command::none();
+        }
         match _message {
             Message::OnMousePressed => {
                 info!("Mouse pressed");
-                self.width = 0f32;
-                self.height = 0f32;
-                self.pressed = true;
-                self.released = false;
+                // Clicks on the toolbar are for its buttons
+                if self.toolbar_bounds().is_some_and(|t| t.contains(self.cursor)) { return Command::none(); }
+                // Annotating, a press inside the selection starts a shape
+                if self.annotating {
+                    self.finish_text();
+                    if self.selection.is_some_and(|s| s.contains(self.cursor)) {
+                        let at = self.global(self.cursor);
+                        match self.tool {
+                            Some(tool) => {
+                                self.editor.start(Annotation::new(tool, at, self.color, self.stroke));
+                                self.drawing = tool != Tool::Text;
+                                self.typing = tool == Tool::Text;
+                            }
+                            // Pick the shape under the cursor and grab it
+                            None       => {
+                                let hit = self.editor.hit(at, HIT_SLOP * self.scale);
+                                self.editor.select(hit);
+                                if let Some(index) = hit {
+                                    self.editor.begin(index);
+                                    self.moving = Some(at);
+                                }
+                            }
+                        }
+                        self.redraw();
+                    }
+                    return Command::none();
+                }
+                let double_click = self.last_release.is_some_and(|t| t.elapsed() < DOUBLE_CLICK);
+                if double_click && self.selection.is_some_and(|s| s.contains(self.cursor)) {
+                    return self.update(Message::Confirm);
+                }
+                // With Ctrl the selection is kept as a region and a new one started
+                if self.add {
+                    if let Some(s) = self.selection.filter(|s| s.width >= 1.0 && s.height >= 1.0) {
+                        self.regions.push(s);
+                        self.selection = None;
+                    }
+                }
+                // Grab the selection if the press is on it, otherwise start a new one
+                self.drag = self.selection
+                    .and_then(|s| selection::hit(s, self.cursor))
+                    .or(Some(Drag::Create(self.cursor)));
+                match self.drag {
+                    Some(Drag::Create(origin)) => {
+                        self.selection = Some(selection::from_corners(origin, origin));
+                        self.preset = None;
+                        self.confirmed = false;
+                    }
+                    // Presets keep their size, grabbing a handle moves them instead
+                    Some(Drag::Resize(_)) if self.preset.is_some() => {
+                        let s = self.selection.unwrap();
+                        self.drag = Some(Drag::Move(self.cursor - Point::new(s.x, s.y)));
+                    }
+                    _ => {}
+                }
                 Command::none()
             }
 
             Message::OnMouseMoved(_point) => {
-                if self.pressed && !self.released {
-                    self.width = _point.x - self.cursor_pressed_position.x;
-                    self.height = _point.y - self.cursor_pressed_position.y;
-                    self.cursor_released_position = _point;
+                self.cursor = _point;
+                if self.drawing {
+                    let to = self.global(_point);
+                    if let Some(annotation) = self.editor.current_mut() { annotation.drag(to); }
+                    self.redraw();
+                    return Command::none();
                 }
-                else if !self.released { 
-                    self.cursor_pressed_position = _point;
+                if let Some(from) = self.moving {
+                    let to = self.global(_point);
+                    if let Some(annotation) = self.editor.current_mut() { annotation.translate(to.0 - from.0, to.1 - from.1); }
+                    self.moving = Some(to);
+                    self.redraw();
+                    return Command::none();
+                }
+                // Edges being dragged stop at the window's, the cursor may leave it while a button is down
+                let edge = Point::new(_point.x.clamp(0.0, self.size.width), _point.y.clamp(0.0, self.size.height));
+                match (self.drag, self.selection) {
+                    (Some(Drag::Create(origin)), _) => {
+                        let s = selection::from_corners(origin, edge);
+                        self.selection = Some(selection::clamp(match self.ratio() {
+                            Some(ratio) => selection::fit_ratio(s, selection::Handle::towards(origin, edge), ratio),
+                            None        => s,
+                        }, self.size));
+                    }
+                    (Some(Drag::Move(offset)), Some(s)) => {
+                        self.selection = Some(selection::clamp(selection::translate(s, offset, _point), self.size));
+                    }
+                    (Some(Drag::Resize(handle)), Some(s)) => {
+                        let (s, handle) = selection::resize(s, handle, edge);
+                        self.selection = Some(selection::clamp(match self.ratio() {
+                            Some(ratio) => selection::fit_ratio(s, handle, ratio),
+                            None        => s,
+                        }, self.size));
+                        self.drag = Some(Drag::Resize(handle));
+                    }
+                    _ => {}
                 }
                 Command::none()
             }
 
             Message::OnMouseReleased => {
                 info!("Mouse released");
-                self.pressed = false;
-                self.released = true;
+                // A drawn or moved shape is done, a text label only once it is typed
+                if self.drawing || self.moving.is_some() {
+                    self.drawing = false;
+                    self.moving = None;
+                    self.editor.commit();
+                }
+                if self.drag.is_none() { return Command::none(); }
+                let clicked = matches!(self.drag, Some(Drag::Create(_)))
+                    && self.selection.is_some_and(|s| s.width < 1.0 || s.height < 1.0);
+                let hovered = self.hovered();
+                self.drag = None;
+                self.last_release = Some(Instant::now());
+                // A click without a drag captures the window under the cursor, if there is one,
+                // as much of it as is on the frame
+                if clicked {
+                    let frame = iced::Rectangle::new(Point::ORIGIN, self.size);
+                    self.selection = hovered.and_then(|w| w.intersection(&frame));
+                    if self.selection.is_some() && !self.add { return self.update(Message::Confirm); }
+                }
                 Command::none()
             }
 
-            _ => { Command::none() }
+            Message::Confirm => {
+                // Confirming again with the toolbar up takes its first action
+                if self.confirmed { return self.update(Message::Act(Action::Save)); }
+                info!("Selection confirmed: {:?} and {} more", self.selection, self.regions.len());
+                self.confirmed = self.selection.is_some() || !self.regions.is_empty();
+                Command::none()
+            }
+
+            Message::Act(action) => {
+                self.finish_text();
+                if action == Action::Annotate {
+                    self.annotating = self.selection.is_some();
+                    self.redraw();
+                } else if self.selection.is_some() || !self.regions.is_empty() {
+                    self.action = Some(action);
+                }
+                Command::none()
+            }
+
+            Message::Tool(tool) => {
+                self.finish_text();
+                self.tool = tool;
+                if tool.is_some() { self.editor.select(None); }
+                Command::none()
+            }
+
+            // Both apply to the picked shape too
+            Message::Color(color) => {
+                self.color = color;
+                if let Some(index) = self.editor.selected() {
+                    self.editor.change(index, |annotation| annotation.color = color);
+                    self.redraw();
+                }
+                Command::none()
+            }
+
+            Message::Stroke(stroke) => {
+                self.stroke = stroke;
+                if let Some(index) = self.editor.selected() {
+                    self.editor.change(index, |annotation| annotation.width = stroke);
+                    self.redraw();
+                }
+                Command::none()
+            }
+
+            Message::Undo => {
+                self.finish_text();
+                if self.editor.undo() { self.redraw(); }
+                Command::none()
+            }
+
+            Message::Redo => {
+                self.finish_text();
+                if self.editor.redo() { self.redraw(); }
+                Command::none()
+            }
+
+            Message::Delete => {
+                if let Some(index) = self.editor.selected() {
+                    self.editor.remove(index);
+                    self.redraw();
+                }
+                Command::none()
+            }
+
+            Message::Char(c) => {
+                match c {
+                    '\r' | '\u{1b}' => self.finish_text(),
+                    c                => if let Some(Annotation { shape: Shape::Text(_, text), .. }) = self.editor.current_mut() {
+                        if c == '\u{8}' { text.pop(); }
+                        else if !c.is_control() { text.push(c); }
+                    },
+                }
+                self.redraw();
+                Command::none()
+            }
+
+            Message::Nudge(offset) => {
+                if let Some(s) = self.selection {
+                    let moved = iced::Rectangle { x: s.x + offset.x, y: s.y + offset.y, ..s };
+                    self.selection = Some(selection::clamp(moved, self.size));
+                }
+                Command::none()
+            }
+
+            Message::Resize(offset) => {
+                if self.preset.is_some() { return Command::none(); }
+                // The bottom right corner stops at the edge of the frame
+                if let Some(s) = self.selection {
+                    self.selection = Some(iced::Rectangle {
+                        width: (s.width + offset.x).clamp(1.0, (self.size.width - s.x).max(1.0)),
+                        height: (s.height + offset.y).clamp(1.0, (self.size.height - s.y).max(1.0)),
+                        ..s
+                    });
+                }
+                Command::none()
+            }
+
+            Message::NextMonitor(backwards) => {
+                if self.monitors.is_empty() { return Command::none(); }
+                let count = self.monitors.len();
+                let next = match (self.monitor, backwards) {
+                    (None, false)    => 0,
+                    (None, true)     => count - 1,
+                    (Some(m), false) => (m + 1) % count,
+                    (Some(m), true)  => (m + count - 1) % count,
+                };
+                self.monitor = Some(next);
+                self.selection = Some(self.monitors[next]);
+                self.preset = None;
+                Command::none()
+            }
+
+            Message::Square(square) => {
+                self.square = square;
+                Command::none()
+            }
+
+            Message::Add(add) => {
+                self.add = add;
+                Command::none()
+            }
+
+            Message::ToggleRatio => {
+                self.ratio_lock = !self.ratio_lock && self.settings.ratio.is_some();
+                info!("Ratio lock: {}", self.ratio_lock);
+                Command::none()
+            }
+
+            Message::Preset(n) => {
+                if let Some((name, width, height)) = self.settings.presets.get(n) {
+                    info!("Preset {} {}x{}", name, width, height);
+                    let size = Size::new(*width as f32 / self.scale, *height as f32 / self.scale);
+                    self.selection = Some(selection::clamp(iced::Rectangle::new(
+                        Point::new(self.cursor.x - size.width / 2.0, self.cursor.y - size.height / 2.0),
+                        size,
+                    ), self.size));
+                    self.drag = None;
+                    self.monitor = None;
+                    self.preset = Some(n);
+                }
+                Command::none()
+            }
         }
     }
 
     fn view(&self) -> Element<Message, Renderer<Theme>> {
+        let editing = !matches!(self.drag, Some(Drag::Create(_)));
+        let frame: Element<Message, Renderer<Theme>> = match &self.frame {
+            Some(frame) => Image::new(frame.clone())
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .content_fit(ContentFit::Fill)
+                .into(),
+            None        => Space::new(Length::Fill, Length::Fill).into(),
+        };
+
+        // Crosshair guides spanning the whole window
+        let vertical = container(Space::new(Length::Fixed(1.0), Length::Fill))
+            .style(style::Container::Guide);
+        let horizontal = container(Space::new(Length::Fill, Length::Fixed(1.0)))
+            .style(style::Container::Guide);
+
+        stack::Stack::with_children(vec![
+            frame,
+            rect::Rectangle::new(self.hovered().or(self.selection))
+                .regions(self.regions.clone())
+                .handles(self.selection.is_some() && editing && self.preset.is_none() && !self.annotating)
+                .border(self.settings.border_width, self.settings.border_color)
+                .into(),
+            annotate::Annotations::new(self.layer.clone().filter(|_| self.annotating),
+                                       self.selection.unwrap_or(iced::Rectangle::with_size(Size::ZERO)))
+                .caret(self.caret())
+                .picked(self.picked())
+                .into(),
+            place(vertical, Point::new(self.cursor.x, 0.0)),
+            place(horizontal, Point::new(0.0, self.cursor.y)),
+            self.readout(),
+            self.loupe(),
+            self.toolbar(),
+        ]).into()
+    }
+
+}
+
+impl App {
+    // Global origin and size of the selection in physical pixels, next to the cursor.
+    fn readout(&self) -> Element<Message, Renderer<Theme>> {
+        let global = |point: Point| Point::new(
+            (self.origin.x + point.x * self.scale).round(),
+            (self.origin.y + point.y * self.scale).round(),
+        );
+        let label = match self.selection {
+            Some(s) => {
+                let origin = global(Point::new(s.x, s.y));
+                let preset = self.preset
+                    .and_then(|n| self.settings.presets.get(n))
+                    .map(|(name, _, _)| format!("  {}", name))
+                    .unwrap_or_default();
+                let lock = if self.ratio().is_some() { "  locked" } else { "" };
+                let regions = if self.regions.is_empty() { String::new() }
+                              else { format!("  +{}", self.regions.len()) };
+                format!("{}, {}  {} × {}{}{}{}", origin.x, origin.y,
+                        (s.width * self.scale).round(), (s.height * self.scale).round(), preset, lock, regions)
+            }
+            None    => {
+                let cursor = global(self.cursor);
+                format!("{}, {}", cursor.x, cursor.y)
+            }
+        };
+
+        // Keep the label on screen by flipping it to the other side of the cursor near the edges
+        let (offset, width, height) = (16.0, label.chars().count() as f32 * 8.0 + 8.0, 24.0);
+        let x = if self.cursor.x + offset + width > self.size.width { self.cursor.x - offset - width }
+                else { self.cursor.x + offset };
+        let y = if self.cursor.y + offset + height > self.size.height { self.cursor.y - offset - height }
+                else { self.cursor.y + offset };
+
+        let label = container(text(label).size(14))
+            .padding([2, 4])
+            .style(style::Container::Label);
+        place(label, Point::new(x, y))
+    }
+}
+
+impl App {
+    // Magnified pixels around the cursor and the hex color under it, above the cursor.
+    fn loupe(&self) -> Element<Message, Renderer<Theme>> {
+        let frame = match &self.settings.frame {
+            Some(frame) => frame,
+            None        => return Space::new(Length::Shrink, Length::Shrink).into(),
+        };
+        // Always odd so there is a center pixel
+        let size = self.settings.loupe_size.unwrap_or(LOUPE_SIZE);
+        if size == 0 { return Space::new(Length::Shrink, Length::Shrink).into(); }
+        let size = size | 1;
+        let zoom = self.settings.loupe_zoom.unwrap_or(LOUPE_ZOOM);
+
+        let pixels = loupe::sample(frame,
+                                   (self.cursor.x * self.scale) as i64,
+                                   (self.cursor.y * self.scale) as i64,
+                                   size);
+        let color = loupe::hex(pixels[pixels.len() / 2]);
+
+        let (offset, side, label) = (16.0, size as f32 * zoom, 24.0);
+        let x = if self.cursor.x + offset + side > self.size.width { self.cursor.x - offset - side }
+                else { self.cursor.x + offset };
+        let y = if self.cursor.y - offset - side - label < 0.0 { self.cursor.y + offset + label }
+                else { self.cursor.y - offset - side - label };
+
         let content = column![
-            rect::Rectangle::new(self.cursor_pressed_position.x, self.cursor_pressed_position.y, self.width, self.height),
-        ]
-        .padding([self.cursor_pressed_position.y, self.cursor_pressed_position.x])
-        .spacing(0)
-        .align_items(Alignment::Start);
-
-        container(content)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .into()
+            loupe::Loupe::new(pixels, size, zoom),
+            container(text(color).size(14))
+                .padding([2, 4])
+                .style(style::Container::Label),
+        ];
+        place(content, Point::new(x, y))
     }
+}
+
+impl App {
+    // The actions for the confirmed selection, next to it.
+    fn toolbar(&self) -> Element<Message, Renderer<Theme>> {
+        let bounds = match self.toolbar_bounds() {
+            Some(bounds) => bounds,
+            None         => return Space::new(Length::Shrink, Length::Shrink).into(),
+        };
+        let actions = self.actions().into_iter().map(|(label, action)| {
+            tool(label, Message::Act(action), style::Button::Default, TOOL_SIZE.width)
+        }).collect();
+        let actions = Row::with_children(actions).spacing(2);
+        if !self.annotating {
+            return place(container(actions).padding(2).style(style::Container::Label), Point::new(bounds.x, bounds.y));
+        }
+
+        let selected = |yes: bool| if yes { style::Button::Selected } else { style::Button::Default };
+        let mut tools: Vec<Element<Message, Renderer<Theme>>> = vec![
+            tool("Select", Message::Tool(None), selected(self.tool.is_none()), TOOL_SIZE.width),
+        ];
+        tools.extend(Tool::ALL.into_iter().map(|t| {
+            tool(t.name(), Message::Tool(Some(t)), selected(Some(t) == self.tool), TOOL_SIZE.width)
+        }));
+        tools.extend(COLORS.into_iter().map(|[r, g, b, a]| {
+            let swatch = iced::Color::from_rgba8(r, g, b, a as f32 / 255.0);
+            button(Space::new(Length::Fill, Length::Fill))
+                .width(Length::Fixed(TOOL_SIZE.height))
+                .height(Length::Fixed(TOOL_SIZE.height))
+                .style(style::Button::Swatch(swatch, [r, g, b, a] == self.color))
+                .on_press(Message::Color([r, g, b, a]))
+                .into()
+        }));
+        tools.extend(STROKES.into_iter().map(|stroke| {
+            tool(format!("{}px", stroke), Message::Stroke(stroke), selected(stroke == self.stroke), STROKE_BUTTON)
+        }));
+        let toolbar = column![Row::with_children(tools).spacing(2), actions].spacing(2);
+        place(container(toolbar).padding(2).style(style::Container::Label), Point::new(bounds.x, bounds.y))
+    }
+}
+
+// A toolbar button of the usual height.
+fn tool<'a>(label: impl ToString, message: Message, style: style::Button, width: f32)
+    -> Element<'a, Message, Renderer<Theme>> {
+    button(text(label).size(14))
+        .width(Length::Fixed(width))
+        .height(Length::Fixed(TOOL_SIZE.height))
+        .style(style)
+        .on_press(message)
+        .into()
+}
 
+// Position `content` with its top left corner at `at`, in logical window coordinates.
+fn place<'a>(content: impl Into<Element<'a, Message, Renderer<Theme>>>, at: Point)
+    -> Element<'a, Message, Renderer<Theme>> {
+    container(content)
+        .padding(Padding { top: at.y.max(0.0), right: 0.0, bottom: 0.0, left: at.x.max(0.0) })
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
 }
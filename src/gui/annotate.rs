@@ -0,0 +1,122 @@
+use iced::advanced::image::{self, Handle};
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer;
+use iced::advanced::widget::{self, Widget};
+use iced::mouse;
+use iced::{Color, Element, Length};
+
+/* The annotations rasterized over the selection, the same pixels that end
+ * up in the capture, with a caret after the text label being typed and a
+ * frame around the picked shape.
+ */
+pub struct Annotations {
+    layer: Option<Handle>,
+    bounds: iced::Rectangle, // where the layer goes, in logical window coordinates
+    caret: Option<iced::Rectangle>,
+    picked: Option<iced::Rectangle>,
+}
+
+impl Annotations {
+    pub fn new(layer: Option<Handle>, bounds: iced::Rectangle) -> Self {
+        Self {
+            layer,
+            bounds,
+            caret: None,
+            picked: None,
+        }
+    }
+
+    // Draw the text caret here.
+    pub fn caret(mut self, caret: Option<iced::Rectangle>) -> Self {
+        self.caret = caret;
+        self
+    }
+
+    // Frame this shape's bounds.
+    pub fn picked(mut self, picked: Option<iced::Rectangle>) -> Self {
+        self.picked = picked;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Appearance {
+    pub caret_color: Color,
+}
+
+pub trait StyleSheet {
+    fn appearance(&self) -> Appearance;
+}
+
+impl<Message, Renderer> Widget<Message, Renderer> for Annotations
+where
+Renderer: renderer::Renderer + image::Renderer<Handle = Handle>,
+Renderer::Theme: StyleSheet,
+{
+    fn width(&self) -> Length {
+        Length::Fill
+    }
+
+    fn height(&self) -> Length {
+        Length::Fill
+    }
+
+    fn layout(
+        &self,
+        _renderer: &Renderer,
+        limits: &layout::Limits,
+        ) -> layout::Node {
+        layout::Node::new(limits.width(Length::Fill).height(Length::Fill).max())
+    }
+    fn draw(
+        &self,
+        _state: &widget::Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &iced::Rectangle,
+        ) {
+        let appearance = theme.appearance();
+        let origin = layout.bounds();
+        if let Some(layer) = &self.layer {
+            renderer.draw(layer.clone(), iced::Rectangle {
+                x: origin.x + self.bounds.x,
+                y: origin.y + self.bounds.y,
+                ..self.bounds
+            });
+        }
+        if let Some(caret) = self.caret {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: iced::Rectangle { x: origin.x + caret.x, y: origin.y + caret.y, ..caret },
+                    border_radius: 0.0.into(),
+                    border_width: 0.0,
+                    border_color: Color::TRANSPARENT,
+                },
+                appearance.caret_color,
+                );
+        }
+        if let Some(picked) = self.picked {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: iced::Rectangle { x: origin.x + picked.x, y: origin.y + picked.y, ..picked },
+                    border_radius: 0.0.into(),
+                    border_width: 1.0,
+                    border_color: appearance.caret_color,
+                },
+                Color::TRANSPARENT,
+                );
+        }
+    }
+}
+impl<'a, Message, Renderer> From<Annotations> for Element<'a, Message, Renderer>
+where
+Renderer: renderer::Renderer + image::Renderer<Handle = Handle>,
+Renderer::Theme: StyleSheet,
+{
+    fn from(annotations: Annotations) -> Self {
+        Self::new(annotations)
+    }
+}
@@ -0,0 +1,254 @@
+use crate::args::annotate::{Annotation, Point};
+
+// One change to the annotations, with what it takes to make or undo it.
+#[derive(Debug, Clone)]
+enum Edit {
+    Add(usize, Annotation),
+    Remove(usize, Annotation),
+    Change(usize, Annotation, Annotation), // before and after
+}
+
+/* The annotations of the selection and their history. A change in
+ * progress, a shape being drawn, typed or moved, stays out of the history
+ * until it is committed, so it undoes in one step.
+ */
+#[derive(Default)]
+pub struct Editor {
+    annotations: Vec<Annotation>, // in global physical pixels, bottom to top
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    pending: Option<(usize, Option<Annotation>)>, // the shape being changed and how it was, none when new
+    selected: Option<usize>,
+}
+
+impl Editor {
+    // Pick up where a project left off, with an empty history.
+    pub fn with(annotations: Vec<Annotation>) -> Editor {
+        Editor { annotations, ..Editor::default() }
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected = index.filter(|&i| i < self.annotations.len());
+    }
+
+    // The topmost shape at `point`.
+    pub fn hit(&self, point: Point, slop: f32) -> Option<usize> {
+        self.annotations.iter().rposition(|a| a.hit(point, slop))
+    }
+
+    // Add a new shape on top, it keeps changing through `current` until committed.
+    pub fn start(&mut self, annotation: Annotation) {
+        self.commit();
+        self.annotations.push(annotation);
+        self.pending = Some((self.annotations.len() - 1, None));
+        self.selected = None;
+    }
+
+    // Start changing the shape at `index` in place.
+    pub fn begin(&mut self, index: usize) {
+        self.commit();
+        if let Some(annotation) = self.annotations.get(index) {
+            self.pending = Some((index, Some(annotation.clone())));
+        }
+    }
+
+    // The shape being changed.
+    pub fn current(&self) -> Option<&Annotation> {
+        self.annotations.get(self.pending.as_ref()?.0)
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut Annotation> {
+        let index = self.pending.as_ref()?.0;
+        self.annotations.get_mut(index)
+    }
+
+    // Put the change in progress into the history, unless it changed nothing.
+    pub fn commit(&mut self) {
+        let (index, before) = match self.pending.take() {
+            Some(pending) => pending,
+            None          => return,
+        };
+        let after = self.annotations[index].clone();
+        match before {
+            None                            => self.record(Edit::Add(index, after)),
+            Some(before) if before != after => self.record(Edit::Change(index, before, after)),
+            Some(_)                         => {}
+        }
+    }
+
+    // Drop the change in progress, a new shape goes away altogether.
+    pub fn discard(&mut self) {
+        match self.pending.take() {
+            Some((index, None))         => { self.annotations.remove(index); }
+            Some((index, Some(before))) => self.annotations[index] = before,
+            None                        => {}
+        }
+    }
+
+    // Change the shape at `index` as one step of the history, e.g. its color.
+    pub fn change(&mut self, index: usize, change: impl FnOnce(&mut Annotation)) {
+        self.begin(index);
+        if let Some(annotation) = self.current_mut() { change(annotation); }
+        self.commit();
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.commit();
+        if index >= self.annotations.len() { return; }
+        let annotation = self.annotations.remove(index);
+        self.record(Edit::Remove(index, annotation));
+        self.selected = None;
+    }
+
+    // Whether there was anything to undo.
+    pub fn undo(&mut self) -> bool {
+        self.commit();
+        let edit = match self.undo.pop() {
+            Some(edit) => edit,
+            None       => return false,
+        };
+        match &edit {
+            Edit::Add(index, _)             => { self.annotations.remove(*index); }
+            Edit::Remove(index, annotation) => self.annotations.insert(*index, annotation.clone()),
+            Edit::Change(index, before, _)  => self.annotations[*index] = before.clone(),
+        }
+        self.redo.push(edit);
+        self.selected = None;
+        true
+    }
+
+    // Whether there was anything to redo.
+    pub fn redo(&mut self) -> bool {
+        self.commit();
+        let edit = match self.redo.pop() {
+            Some(edit) => edit,
+            None       => return false,
+        };
+        match &edit {
+            Edit::Add(index, annotation)  => self.annotations.insert(*index, annotation.clone()),
+            Edit::Remove(index, _)        => { self.annotations.remove(*index); }
+            Edit::Change(index, _, after) => self.annotations[*index] = after.clone(),
+        }
+        self.undo.push(edit);
+        self.selected = None;
+        true
+    }
+
+    // A new edit makes whatever was undone unreachable.
+    fn record(&mut self, edit: Edit) {
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::annotate::{Shape, Tool};
+
+    fn line(x: f32) -> Annotation {
+        let mut annotation = Annotation::new(Tool::Line, (x, 0.0), [255, 0, 0, 255], 2.0);
+        annotation.drag((x + 10.0, 10.0));
+        annotation
+    }
+
+    fn drawn(lines: &[f32]) -> Editor {
+        let mut editor = Editor::default();
+        for x in lines {
+            editor.start(line(*x));
+            editor.commit();
+        }
+        editor
+    }
+
+    #[test]
+    fn undo_and_redo_an_added_shape() {
+        let mut editor = drawn(&[0.0, 20.0]);
+        assert!(editor.undo());
+        assert_eq!(editor.annotations(), [line(0.0)]);
+        assert!(editor.redo());
+        assert_eq!(editor.annotations(), [line(0.0), line(20.0)]);
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn undo_past_the_start_does_nothing() {
+        let mut editor = drawn(&[0.0]);
+        assert!(editor.undo());
+        assert!(!editor.undo());
+        assert!(editor.annotations().is_empty());
+    }
+
+    #[test]
+    fn a_shape_in_progress_undoes_in_one_step() {
+        let mut editor = Editor::default();
+        editor.start(Annotation::new(Tool::Pen, (0.0, 0.0), [0, 0, 0, 255], 2.0));
+        for x in 1..10 {
+            editor.current_mut().unwrap().drag((x as f32, 0.0));
+        }
+        // Undo commits it first, then takes it back whole
+        assert!(editor.undo());
+        assert!(editor.annotations().is_empty());
+        assert!(editor.redo());
+        assert!(matches!(&editor.annotations()[0].shape, Shape::Pen(points) if points.len() == 10));
+    }
+
+    #[test]
+    fn undo_a_change() {
+        let mut editor = drawn(&[0.0]);
+        editor.change(0, |annotation| annotation.color = [0, 0, 255, 255]);
+        assert_eq!(editor.annotations()[0].color, [0, 0, 255, 255]);
+        assert!(editor.undo());
+        assert_eq!(editor.annotations()[0].color, [255, 0, 0, 255]);
+        assert!(editor.redo());
+        assert_eq!(editor.annotations()[0].color, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn moving_back_to_where_it_was_is_no_change() {
+        let mut editor = drawn(&[0.0]);
+        editor.begin(0);
+        editor.current_mut().unwrap().translate(5.0, 5.0);
+        editor.current_mut().unwrap().translate(-5.0, -5.0);
+        editor.commit();
+        assert!(editor.undo());
+        assert!(editor.annotations().is_empty());
+    }
+
+    #[test]
+    fn undo_a_removal_in_place() {
+        let mut editor = drawn(&[0.0, 20.0, 40.0]);
+        editor.remove(1);
+        assert_eq!(editor.annotations(), [line(0.0), line(40.0)]);
+        assert!(editor.undo());
+        assert_eq!(editor.annotations(), [line(0.0), line(20.0), line(40.0)]);
+    }
+
+    #[test]
+    fn discarding_a_new_shape_leaves_no_history() {
+        let mut editor = drawn(&[0.0]);
+        editor.start(line(20.0));
+        editor.discard();
+        assert_eq!(editor.annotations(), [line(0.0)]);
+        assert!(editor.undo());
+        assert!(editor.annotations().is_empty());
+    }
+
+    #[test]
+    fn a_new_edit_drops_what_was_undone() {
+        let mut editor = drawn(&[0.0, 20.0]);
+        assert!(editor.undo());
+        editor.start(line(40.0));
+        editor.commit();
+        assert!(!editor.redo());
+        assert_eq!(editor.annotations(), [line(0.0), line(40.0)]);
+    }
+}
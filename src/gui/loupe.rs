@@ -0,0 +1,144 @@
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer;
+use iced::advanced::widget::{self, Widget};
+use iced::mouse;
+use iced::{Color, Element, Length, Size};
+
+/* A magnified N×N neighborhood of the frozen frame around the cursor,
+ * every pixel drawn as a `zoom` sized cell with a grid between them and
+ * the pixel under the cursor outlined in the middle.
+ */
+pub struct Loupe {
+    pixels: Vec<Color>, // row major, `size` × `size`
+    size: usize,
+    zoom: f32,
+}
+
+impl Loupe {
+    pub fn new(pixels: Vec<Color>, size: usize, zoom: f32) -> Self {
+        Self {
+            pixels,
+            size,
+            zoom,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Appearance {
+    pub grid_color: Color,
+    pub border_color: Color,
+    pub center_color: Color,
+}
+
+pub trait StyleSheet {
+    fn appearance(&self) -> Appearance;
+}
+
+// Colors of the `size` × `size` pixels centered on (`x`, `y`), transparent outside of the frame.
+pub fn sample(frame: &image::RgbaImage, x: i64, y: i64, size: usize) -> Vec<Color> {
+    let half = (size / 2) as i64;
+    let mut pixels = Vec::with_capacity(size * size);
+    for dy in -half..=half {
+        for dx in -half..=half {
+            let (px, py) = (x + dx, y + dy);
+            let inside = px >= 0 && py >= 0 && px < frame.width() as i64 && py < frame.height() as i64;
+            pixels.push(if inside {
+                let [r, g, b, a] = frame.get_pixel(px as u32, py as u32).0;
+                Color::from_rgba8(r, g, b, a as f32 / 255.0)
+            } else {
+                Color::TRANSPARENT
+            });
+        }
+    }
+    pixels
+}
+
+// `#rrggbb` of a color.
+pub fn hex(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+impl<Message, Renderer> Widget<Message, Renderer> for Loupe
+where
+Renderer: renderer::Renderer,
+Renderer::Theme: StyleSheet,
+{
+    fn width(&self) -> Length {
+        Length::Shrink
+    }
+
+    fn height(&self) -> Length {
+        Length::Shrink
+    }
+
+    fn layout(
+        &self,
+        _renderer: &Renderer,
+        _limits: &layout::Limits,
+        ) -> layout::Node {
+        let side = self.size as f32 * self.zoom;
+        layout::Node::new(Size::new(side, side))
+    }
+    fn draw(
+        &self,
+        _state: &widget::Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &iced::Rectangle,
+        ) {
+        let appearance = theme.appearance();
+        let bounds = layout.bounds();
+        let cell = |column: usize, row: usize| iced::Rectangle {
+            x: bounds.x + column as f32 * self.zoom,
+            y: bounds.y + row as f32 * self.zoom,
+            width: self.zoom,
+            height: self.zoom,
+        };
+
+        for (i, color) in self.pixels.iter().enumerate() {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: cell(i % self.size, i / self.size),
+                    border_radius: 0.0.into(),
+                    border_width: 0.5,
+                    border_color: appearance.grid_color,
+                },
+                *color,
+                );
+        }
+
+        let center = self.size / 2;
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds: cell(center, center),
+                border_radius: 0.0.into(),
+                border_width: 1.5,
+                border_color: appearance.center_color,
+            },
+            Color::TRANSPARENT,
+            );
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border_radius: 0.0.into(),
+                border_width: 2.0,
+                border_color: appearance.border_color,
+            },
+            Color::TRANSPARENT,
+            );
+    }
+}
+impl<'a, Message, Renderer> From<Loupe> for Element<'a, Message, Renderer>
+where
+Renderer: renderer::Renderer,
+Renderer::Theme: StyleSheet,
+{
+    fn from(loupe: Loupe) -> Self {
+        Self::new(loupe)
+    }
+}
@@ -0,0 +1,72 @@
+use iced::widget::image::{Handle, Image};
+use iced::{executor, keyboard, mouse, subscription, window};
+use iced::{Application, Command, ContentFit, Event, Length, Subscription};
+
+use crate::gui::theme::Theme;
+
+/* A capture kept on screen in an undecorated window above the others.
+ * Dragging moves it, Escape or a right click closes it.
+ */
+pub fn run(image: image::RgbaImage) -> iced::Result {
+    let size = (image.width(), image.height());
+    Pin::run(iced::Settings {
+        window: window::Settings {
+            size,
+            resizable: false,
+            decorations: false,
+            level: window::Level::AlwaysOnTop,
+            ..Default::default()
+        },
+        ..iced::Settings::with_flags(image)
+    })
+}
+
+struct Pin {
+    image: Handle,
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    Drag,
+    Close,
+}
+
+impl Application for Pin {
+    type Executor = executor::Default;
+    type Message = Message;
+    type Theme = Theme;
+    type Flags = image::RgbaImage;
+
+    fn new(image: image::RgbaImage) -> (Pin, Command<Message>) {
+        let image = Handle::from_pixels(image.width(), image.height(), image.into_raw());
+        (Pin { image }, Command::none())
+    }
+
+    fn title(&self) -> String {
+        String::from("Pictura")
+    }
+
+    fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::Drag  => window::drag(),
+            Message::Close => window::close(),
+        }
+    }
+
+    fn view(&self) -> iced::Element<'_, Message, iced::Renderer<Theme>> {
+        Image::new(self.image.clone())
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .content_fit(ContentFit::Fill)
+            .into()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        subscription::events_with(|event, _status| match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))  => Some(Message::Drag),
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) => Some(Message::Close),
+            Event::Keyboard(keyboard::Event::KeyPressed { key_code: keyboard::KeyCode::Escape, .. }) => Some(Message::Close),
+            _ => None,
+        })
+    }
+}
@@ -2,65 +2,155 @@ use iced::advanced::layout::{self, Layout};
 use iced::advanced::renderer;
 use iced::advanced::widget::{self, Widget};
 use iced::mouse;
-use iced::{Color, Element, Length, Size};
+use iced::{Color, Element, Length};
 
+use crate::gui::selection::{self, Handle};
+
+/* Covers the whole window: everything outside the selection and the
+ * other regions is dimmed, they stay clear so the frozen frame below
+ * shows through untouched, each with a border and the selection
+ * optionally with its resize handles.
+ */
 pub struct Rectangle {
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
+    selection: Option<iced::Rectangle>,
+    regions: Vec<iced::Rectangle>,
+    handles: bool,
+    border_width: Option<f32>,
+    border_color: Option<Color>,
 }
 
 impl Rectangle {
-    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
-        Self { 
-            x,
-            y,
-            width,
-            height,
+    pub fn new(selection: Option<iced::Rectangle>) -> Self {
+        Self {
+            selection,
+            regions: Vec::new(),
+            handles: false,
+            border_width: None,
+            border_color: None,
         }
     }
+
+    // Earlier selections kept clear and bordered next to the current one.
+    pub fn regions(mut self, regions: Vec<iced::Rectangle>) -> Self {
+        self.regions = regions;
+        self
+    }
+
+    // Draw the eight resize handles on the edges.
+    pub fn handles(mut self, handles: bool) -> Self {
+        self.handles = handles;
+        self
+    }
+
+    // Override the border width and color of the style sheet.
+    pub fn border(mut self, width: Option<f32>, color: Option<Color>) -> Self {
+        self.border_width = width;
+        self.border_color = color;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Appearance {
+    pub dim: Color,
+    pub border_width: f32,
+    pub border_color: Color,
+    pub handle_color: Color,
 }
 
-impl<Message, Renderer> Widget<Message, Renderer> for Rectangle 
+pub trait StyleSheet {
+    fn appearance(&self) -> Appearance;
+}
+
+impl<Message, Renderer> Widget<Message, Renderer> for Rectangle
 where
 Renderer: renderer::Renderer,
+Renderer::Theme: StyleSheet,
 {
     fn width(&self) -> Length {
-        Length::Shrink
+        Length::Fill
     }
 
     fn height(&self) -> Length {
-        Length::Shrink
+        Length::Fill
     }
 
     fn layout(
         &self,
         _renderer: &Renderer,
-        _limits: &layout::Limits,
+        limits: &layout::Limits,
         ) -> layout::Node {
-        layout::Node::new(Size::new(self.width, self.height))
-    }       
+        layout::Node::new(limits.width(Length::Fill).height(Length::Fill).max())
+    }
     fn draw(
         &self,
         _state: &widget::Tree,
         renderer: &mut Renderer,
-        _theme: &Renderer::Theme,
+        theme: &Renderer::Theme,
         _style: &renderer::Style,
         layout: Layout<'_>,
         _cursor: mouse::Cursor,
         _viewport: &iced::Rectangle,
         ) {
-        renderer.fill_quad(
-            renderer::Quad {
-                bounds: layout.bounds(),
-                border_radius: 2.0.into(),
-                border_width: 0.0,
-                border_color: Color::TRANSPARENT,
-            },
-            Color::new(0.0, 0.0, 0.0, 0.6),
-            );
+        let mut appearance = theme.appearance();
+        appearance.border_width = self.border_width.unwrap_or(appearance.border_width);
+        appearance.border_color = self.border_color.unwrap_or(appearance.border_color);
+        let bounds = layout.bounds();
+        let quad = |bounds: iced::Rectangle| renderer::Quad {
+            bounds,
+            border_radius: 0.0.into(),
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        };
 
+        let holes: Vec<iced::Rectangle> = self.regions
+            .iter()
+            .copied()
+            .chain(self.selection)
+            .filter_map(|r| r.intersection(&bounds))
+            .collect();
+        for band in uncovered(bounds, &holes) {
+            renderer.fill_quad(quad(band), appearance.dim);
+        }
+
+        if appearance.border_width > 0.0 {
+            // Drawn just outside each region so it never covers captured pixels
+            let width = appearance.border_width;
+            for hole in &holes {
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: iced::Rectangle {
+                            x: hole.x - width,
+                            y: hole.y - width,
+                            width: hole.width + 2.0 * width,
+                            height: hole.height + 2.0 * width,
+                        },
+                        border_radius: 0.0.into(),
+                        border_width: width,
+                        border_color: appearance.border_color,
+                    },
+                    Color::TRANSPARENT,
+                    );
+            }
+        }
+
+        let selection = match self.selection.and_then(|s| s.intersection(&bounds)) {
+            Some(selection) => selection,
+            None            => return,
+        };
+        if self.handles {
+            for handle in Handle::ALL {
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: handle.bounds(selection),
+                        border_radius: 1.0.into(),
+                        border_width: 1.0,
+                        border_color: appearance.border_color,
+                    },
+                    appearance.handle_color,
+                    );
+            }
+        }
     }
     fn mouse_interaction(
         &self,
@@ -73,9 +163,45 @@ Renderer: renderer::Renderer,
         iced::mouse::Interaction::Crosshair // Change cursor to crosshair on hover
     }
 }
+/* The parts of `bounds` outside every hole. The edges of the holes cut
+ * `bounds` into a grid, uncovered cells are joined into runs per row.
+ */
+fn uncovered(bounds: iced::Rectangle, holes: &[iced::Rectangle]) -> Vec<iced::Rectangle> {
+    let edges = |start: f32, end: f32, sides: &dyn Fn(&iced::Rectangle) -> [f32; 2]| {
+        let mut edges: Vec<f32> = holes.iter().flat_map(sides).chain([start, end]).collect();
+        edges.sort_by(|a, b| a.total_cmp(b));
+        edges.dedup();
+        edges
+    };
+    let xs = edges(bounds.x, bounds.x + bounds.width, &|h| [h.x, h.x + h.width]);
+    let ys = edges(bounds.y, bounds.y + bounds.height, &|h| [h.y, h.y + h.height]);
+
+    let mut bands = Vec::new();
+    for row in ys.windows(2) {
+        let mut run: Option<f32> = None;
+        for column in xs.windows(2) {
+            let center = iced::Point::new((column[0] + column[1]) / 2.0, (row[0] + row[1]) / 2.0);
+            let covered = holes.iter().any(|h| h.contains(center));
+            match (covered, run) {
+                (false, None)       => run = Some(column[0]),
+                (true, Some(start)) => {
+                    bands.push(selection::from_corners(iced::Point::new(start, row[0]), iced::Point::new(column[0], row[1])));
+                    run = None;
+                }
+                _                   => {}
+            }
+        }
+        if let Some(start) = run {
+            bands.push(selection::from_corners(iced::Point::new(start, row[0]), iced::Point::new(xs[xs.len() - 1], row[1])));
+        }
+    }
+    bands
+}
+
 impl<'a, Message, Renderer> From<Rectangle> for Element<'a, Message, Renderer>
 where
 Renderer: renderer::Renderer,
+Renderer::Theme: StyleSheet,
 {
     fn from(rectangle: Rectangle) -> Self {
         Self::new(rectangle)
@@ -0,0 +1,220 @@
+use iced::{Point, Rectangle, Size, Vector};
+
+// Side length of the square grab handles, in logical pixels.
+pub const HANDLE_SIZE: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handle {
+    TopLeft,
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
+}
+
+impl Handle {
+    pub const ALL: [Handle; 8] = [
+        Handle::TopLeft, Handle::Top, Handle::TopRight, Handle::Right,
+        Handle::BottomRight, Handle::Bottom, Handle::BottomLeft, Handle::Left,
+    ];
+
+    // Center of the handle on the edge of `bounds`.
+    pub fn position(&self, bounds: Rectangle) -> Point {
+        let (left, right) = (bounds.x, bounds.x + bounds.width);
+        let (top, bottom) = (bounds.y, bounds.y + bounds.height);
+        let (center_x, center_y) = (bounds.center_x(), bounds.center_y());
+        match self {
+            Handle::TopLeft     => Point::new(left, top),
+            Handle::Top         => Point::new(center_x, top),
+            Handle::TopRight    => Point::new(right, top),
+            Handle::Right       => Point::new(right, center_y),
+            Handle::BottomRight => Point::new(right, bottom),
+            Handle::Bottom      => Point::new(center_x, bottom),
+            Handle::BottomLeft  => Point::new(left, bottom),
+            Handle::Left        => Point::new(left, center_y),
+        }
+    }
+
+    // Square the handle is drawn in and grabbed by.
+    pub fn bounds(&self, selection: Rectangle) -> Rectangle {
+        let center = self.position(selection);
+        Rectangle::new(
+            Point::new(center.x - HANDLE_SIZE / 2.0, center.y - HANDLE_SIZE / 2.0),
+            Size::new(HANDLE_SIZE, HANDLE_SIZE),
+        )
+    }
+
+    // Edges the handle moves as (left, top, right, bottom).
+    fn edges(&self) -> (bool, bool, bool, bool) {
+        match self {
+            Handle::TopLeft     => (true,  true,  false, false),
+            Handle::Top         => (false, true,  false, false),
+            Handle::TopRight    => (false, true,  true,  false),
+            Handle::Right       => (false, false, true,  false),
+            Handle::BottomRight => (false, false, true,  true),
+            Handle::Bottom      => (false, false, false, true),
+            Handle::BottomLeft  => (true,  false, false, true),
+            Handle::Left        => (true,  false, false, false),
+        }
+    }
+
+    // The corner dragged when spanning a selection from `origin` to `cursor`.
+    pub fn towards(origin: Point, cursor: Point) -> Handle {
+        let (left, top) = (cursor.x < origin.x, cursor.y < origin.y);
+        Handle::from_edges((left, top, !left, !top))
+    }
+
+    fn from_edges(edges: (bool, bool, bool, bool)) -> Handle {
+        Handle::ALL.into_iter().find(|handle| handle.edges() == edges).unwrap_or(Handle::BottomRight)
+    }
+}
+
+// What the current drag is doing to the selection.
+#[derive(Debug, Clone, Copy)]
+pub enum Drag {
+    Create(Point), // spanning a new selection from this corner
+    Move(Vector),  // moving it, the cursor's offset from the top left corner
+    Resize(Handle),
+}
+
+// The rectangle spanned by two opposite corners, in any order.
+pub fn from_corners(a: Point, b: Point) -> Rectangle {
+    Rectangle {
+        x: a.x.min(b.x),
+        y: a.y.min(b.y),
+        width: (a.x - b.x).abs(),
+        height: (a.y - b.y).abs(),
+    }
+}
+
+// What pressing at `cursor` grabs: a handle, the interior or nothing.
+pub fn hit(selection: Rectangle, cursor: Point) -> Option<Drag> {
+    if let Some(handle) = Handle::ALL.into_iter().find(|h| h.bounds(selection).contains(cursor)) {
+        return Some(Drag::Resize(handle));
+    }
+    if selection.contains(cursor) {
+        return Some(Drag::Move(cursor - Point::new(selection.x, selection.y)));
+    }
+    None
+}
+
+/* Move the edges belonging to `handle` to the cursor. Dragging an edge
+ * past the opposite one flips the selection, the returned handle is the
+ * one now under the cursor.
+ */
+pub fn resize(selection: Rectangle, handle: Handle, cursor: Point) -> (Rectangle, Handle) {
+    let (moves_left, moves_top, moves_right, moves_bottom) = handle.edges();
+    let mut left = selection.x;
+    let mut top = selection.y;
+    let mut right = selection.x + selection.width;
+    let mut bottom = selection.y + selection.height;
+    if moves_left   { left = cursor.x; }
+    if moves_top    { top = cursor.y; }
+    if moves_right  { right = cursor.x; }
+    if moves_bottom { bottom = cursor.y; }
+
+    let flip_x = left > right;
+    let flip_y = top > bottom;
+    let handle = Handle::from_edges((
+        if flip_x { moves_right } else { moves_left },
+        if flip_y { moves_bottom } else { moves_top },
+        if flip_x { moves_left } else { moves_right },
+        if flip_y { moves_top } else { moves_bottom },
+    ));
+    (from_corners(Point::new(left, top), Point::new(right, bottom)), handle)
+}
+
+// Put the selection's top left corner at `cursor - offset`.
+pub fn translate(selection: Rectangle, offset: Vector, cursor: Point) -> Rectangle {
+    Rectangle { x: cursor.x - offset.x, y: cursor.y - offset.y, ..selection }
+}
+
+// Move the selection back inside `size`, shrinking it only where it is larger.
+pub fn clamp(selection: Rectangle, size: Size) -> Rectangle {
+    let width = selection.width.min(size.width);
+    let height = selection.height.min(size.height);
+    Rectangle {
+        x: selection.x.clamp(0.0, size.width - width),
+        y: selection.y.clamp(0.0, size.height - height),
+        width,
+        height,
+    }
+}
+
+/* Bring the selection to `ratio` (width / height) while keeping the edges
+ * opposite `handle` in place. A corner shrinks the selection to fit, an
+ * edge derives the other side from the one being dragged.
+ */
+pub fn fit_ratio(selection: Rectangle, handle: Handle, ratio: f32) -> Rectangle {
+    let (left, top, right, bottom) = handle.edges();
+    let (mut width, mut height) = (selection.width, selection.height);
+    match (left || right, top || bottom) {
+        (true, true)  => {
+            if width > height * ratio { width = height * ratio; } else { height = width / ratio; }
+        }
+        (true, false) => height = width / ratio,
+        (false, true) => width = height * ratio,
+        _             => {}
+    }
+    let x = if left { selection.x + selection.width - width }
+            else if right { selection.x }
+            else { selection.center_x() - width / 2.0 };
+    let y = if top { selection.y + selection.height - height }
+            else if bottom { selection.y }
+            else { selection.center_y() - height / 2.0 };
+    Rectangle { x, y, width, height }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rectangle {
+        Rectangle { x, y, width, height }
+    }
+
+    #[test]
+    fn clamp_keeps_a_selection_inside() {
+        let size = Size::new(100.0, 50.0);
+        assert_eq!(clamp(rect(10.0, 10.0, 20.0, 20.0), size), rect(10.0, 10.0, 20.0, 20.0));
+    }
+
+    #[test]
+    fn clamp_moves_it_back_past_the_top_left() {
+        let size = Size::new(100.0, 50.0);
+        assert_eq!(clamp(rect(-5.0, -1.0, 20.0, 20.0), size), rect(0.0, 0.0, 20.0, 20.0));
+    }
+
+    #[test]
+    fn clamp_moves_it_back_past_the_bottom_right() {
+        let size = Size::new(100.0, 50.0);
+        assert_eq!(clamp(rect(90.0, 40.0, 20.0, 20.0), size), rect(80.0, 30.0, 20.0, 20.0));
+    }
+
+    #[test]
+    fn clamp_shrinks_one_larger_than_the_frame() {
+        let size = Size::new(100.0, 50.0);
+        assert_eq!(clamp(rect(-10.0, 5.0, 120.0, 20.0), size), rect(0.0, 5.0, 100.0, 20.0));
+    }
+
+    #[test]
+    fn fit_ratio_shrinks_to_fit_at_a_corner() {
+        // The top left corner is dragged, the bottom right one stays put
+        let fitted = fit_ratio(rect(0.0, 0.0, 100.0, 100.0), Handle::TopLeft, 2.0);
+        assert_eq!(fitted, rect(0.0, 50.0, 100.0, 50.0));
+        let fitted = fit_ratio(rect(0.0, 0.0, 300.0, 100.0), Handle::BottomRight, 2.0);
+        assert_eq!(fitted, rect(0.0, 0.0, 200.0, 100.0));
+    }
+
+    #[test]
+    fn fit_ratio_derives_the_other_side_at_an_edge() {
+        // Around the center of the edges that aren't dragged
+        let fitted = fit_ratio(rect(0.0, 0.0, 160.0, 10.0), Handle::Right, 16.0 / 9.0);
+        assert_eq!(fitted, rect(0.0, -40.0, 160.0, 90.0));
+        let fitted = fit_ratio(rect(0.0, 0.0, 10.0, 90.0), Handle::Top, 16.0 / 9.0);
+        assert_eq!(fitted, rect(-75.0, 0.0, 160.0, 90.0));
+    }
+}
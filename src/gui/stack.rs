@@ -0,0 +1,128 @@
+use iced::advanced::layout::{self, Layout};
+use iced::advanced::renderer;
+use iced::advanced::widget::{self, Operation, Tree};
+use iced::advanced::{Clipboard, Shell, Widget};
+use iced::event::{self, Event};
+use iced::mouse;
+use iced::{Element, Length, Rectangle};
+
+// Layers its children on top of each other, each one filling the whole stack.
+pub struct Stack<'a, Message, Renderer> {
+    children: Vec<Element<'a, Message, Renderer>>,
+}
+
+impl<'a, Message, Renderer> Stack<'a, Message, Renderer> {
+    // The first child is the bottom layer.
+    pub fn with_children(children: Vec<Element<'a, Message, Renderer>>) -> Self {
+        Self { children }
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for Stack<'a, Message, Renderer>
+where
+Renderer: renderer::Renderer,
+{
+    fn width(&self) -> Length {
+        Length::Fill
+    }
+
+    fn height(&self) -> Length {
+        Length::Fill
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        self.children.iter().map(Tree::new).collect()
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(&self.children);
+    }
+
+    fn layout(
+        &self,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+        ) -> layout::Node {
+        let limits = limits.width(Length::Fill).height(Length::Fill);
+        let children = self.children
+            .iter()
+            .map(|child| child.as_widget().layout(renderer, &limits))
+            .collect();
+        layout::Node::with_children(limits.max(), children)
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation<Message>,
+        ) {
+        for ((child, state), layout) in self.children.iter().zip(&mut tree.children).zip(layout.children()) {
+            child.as_widget().operate(state, layout, renderer, operation);
+        }
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+        ) -> event::Status {
+        self.children
+            .iter_mut()
+            .zip(&mut tree.children)
+            .zip(layout.children())
+            .map(|((child, state), layout)| {
+                child.as_widget_mut().on_event(state, event.clone(), layout, cursor, renderer, clipboard, shell, viewport)
+            })
+            .fold(event::Status::Ignored, event::Status::merge)
+    }
+
+    fn draw(
+        &self,
+        tree: &widget::Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        ) {
+        for ((child, state), layout) in self.children.iter().zip(&tree.children).zip(layout.children()) {
+            child.as_widget().draw(state, renderer, theme, style, layout, cursor, viewport);
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &widget::Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer
+        ) -> mouse::Interaction {
+        self.children
+            .iter()
+            .zip(&tree.children)
+            .zip(layout.children())
+            .map(|((child, state), layout)| child.as_widget().mouse_interaction(state, layout, cursor, viewport, renderer))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl<'a, Message, Renderer> From<Stack<'a, Message, Renderer>> for Element<'a, Message, Renderer>
+where
+Message: 'a,
+Renderer: renderer::Renderer + 'a,
+{
+    fn from(stack: Stack<'a, Message, Renderer>) -> Self {
+        Self::new(stack)
+    }
+}
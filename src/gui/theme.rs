@@ -5,11 +5,13 @@ pub mod widget {
     pub type Element<'a, Message, Renderer> = iced_winit::core::Element<'a, Message, Renderer>;
     pub type Container<'a, Message> = iced::widget::Container<'a, Message, Renderer>;
     pub type Button<'a, Message> = iced::widget::Button<'a, Message, Renderer>;
+    pub type Text<'a> = iced::widget::Text<'a, Renderer>;
     pub type Cursor = iced::mouse::Cursor;
 }
 
-use iced::widget::container;
-use iced::{ application, color };
+use iced::widget::{ button, container, text };
+use iced::{ application, color, Background, Color };
+use crate::gui::{ annotate, loupe, rectangle };
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Theme;
@@ -18,7 +20,8 @@ impl application::StyleSheet for Theme {
     type Style = ();
     fn appearance(&self, _style: &Self::Style) -> application::Appearance {
         application::Appearance {
-            background_color: color!(0x28, 0x28, 0x28, 0.7),
+            // The frozen frame covers the window, dimming is up to the selection widget
+            background_color: Color::TRANSPARENT,
             text_color: color!(0xeb, 0xdb, 0xb2),
         }
     }
@@ -28,6 +31,8 @@ pub enum Container {
     #[default]
     Default,
     Bordered,
+    Guide, // crosshair lines following the cursor
+    Label, // readouts floating over the screen
 }
 impl container::StyleSheet for Theme {
     type Style = Container;
@@ -39,6 +44,96 @@ impl container::StyleSheet for Theme {
                 border_color: color!(0x45, 0x85, 0x88),
                 ..Default::default()
             },
+            Container::Guide => container::Appearance {
+                background: Some(Background::Color(color!(0xeb, 0xdb, 0xb2, 0.5))),
+                ..Default::default()
+            },
+            Container::Label => container::Appearance {
+                text_color: Some(color!(0xeb, 0xdb, 0xb2)),
+                background: Some(Background::Color(color!(0x28, 0x28, 0x28, 0.9))),
+                border_radius: 3.0.into(),
+                border_width: 1.0,
+                border_color: color!(0x45, 0x85, 0x88),
+            },
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Button {
+    #[default]
+    Default,
+    Selected,             // the current tool or stroke width
+    Swatch(Color, bool),  // a stroke color, outlined when it is the current one
+}
+impl button::StyleSheet for Theme {
+    type Style = Button;
+
+    fn active(&self, style: &Self::Style) -> button::Appearance {
+        let appearance = button::Appearance {
+            background: Some(Background::Color(color!(0x3c, 0x38, 0x36))),
+            border_radius: 3.0.into(),
+            text_color: color!(0xeb, 0xdb, 0xb2),
+            ..Default::default()
+        };
+        match style {
+            Button::Default                 => appearance,
+            Button::Selected                => button::Appearance {
+                background: Some(Background::Color(color!(0x45, 0x85, 0x88))),
+                ..appearance
+            },
+            Button::Swatch(color, selected) => button::Appearance {
+                background: Some(Background::Color(*color)),
+                border_width: if *selected { 2.0 } else { 0.0 },
+                border_color: color!(0xeb, 0xdb, 0xb2),
+                ..appearance
+            },
+        }
+    }
+
+    fn hovered(&self, style: &Self::Style) -> button::Appearance {
+        match style {
+            Button::Swatch(..) => button::Appearance {
+                border_width: 2.0,
+                border_color: color!(0x45, 0x85, 0x88),
+                ..self.active(style)
+            },
+            _                  => button::Appearance {
+                background: Some(Background::Color(color!(0x45, 0x85, 0x88))),
+                ..self.active(style)
+            },
+        }
+    }
+}
+impl text::StyleSheet for Theme {
+    type Style = ();
+
+    fn appearance(&self, _style: Self::Style) -> text::Appearance {
+        text::Appearance { color: None }
+    }
+}
+impl rectangle::StyleSheet for Theme {
+    fn appearance(&self) -> rectangle::Appearance {
+        rectangle::Appearance {
+            dim: color!(0x28, 0x28, 0x28, 0.7),
+            border_width: 1.0,
+            border_color: color!(0x45, 0x85, 0x88),
+            handle_color: color!(0xeb, 0xdb, 0xb2),
+        }
+    }
+}
+impl annotate::StyleSheet for Theme {
+    fn appearance(&self) -> annotate::Appearance {
+        annotate::Appearance {
+            caret_color: color!(0xeb, 0xdb, 0xb2),
+        }
+    }
+}
+impl loupe::StyleSheet for Theme {
+    fn appearance(&self) -> loupe::Appearance {
+        loupe::Appearance {
+            grid_color: color!(0x28, 0x28, 0x28, 0.5),
+            border_color: color!(0x45, 0x85, 0x88),
+            center_color: color!(0xfb, 0x49, 0x34),
         }
     }
 }